@@ -1,14 +1,24 @@
+use base64::Engine;
+use borsh::BorshDeserialize;
 use clap::{Parser, Subcommand};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
     system_instruction,
     transaction::Transaction,
 };
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
 use std::str::FromStr;
 use swapv3::instruction::SwapV3Instruction;
-use swapv3::state::{Pool, Position, Tick};
+use swapv3::oracle::ObserveResult;
+use swapv3::state::{
+    AccountType, Observations, Pool, Position, PositionMetadata, Tick, TickBitmap,
+};
+use swapv3::tick::CumulativesInside;
+use swapv3::tick_bitmap;
+use swapv3::utils::{SqrtPriceX64, MIN_TICK};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -34,19 +44,34 @@ enum Commands {
         token_a_mint: String,
         #[clap(long)]
         token_b_mint: String,
+        /// The initial price of the pool, in token_1 per token_0.
         #[clap(long)]
-        initial_price: u128,
+        initial_price: f64,
+        /// The number of price observations the pool's oracle can hold.
+        #[clap(long, default_value = "1")]
+        observation_cardinality: usize,
     },
-    /// Adds liquidity to a pool.
+    /// Adds liquidity to a pool, either as a raw liquidity amount or
+    /// derived from the desired token amounts.
     AddLiquidity {
         #[clap(long)]
         pool_address: String,
-        #[clap(long)]
-        amount: u128,
+        #[clap(long, required_unless_present_any = &["amount_0", "amount_1"])]
+        amount: Option<u128>,
+        #[clap(long, conflicts_with = "amount")]
+        amount_0: Option<u64>,
+        #[clap(long, conflicts_with = "amount")]
+        amount_1: Option<u64>,
         #[clap(long)]
         tick_lower: i32,
         #[clap(long)]
         tick_upper: i32,
+        /// The token_0 account paying the deposit.
+        #[clap(long)]
+        token_0_account: String,
+        /// The token_1 account paying the deposit.
+        #[clap(long)]
+        token_1_account: String,
     },
     /// Swaps tokens in a pool.
     Swap {
@@ -56,9 +81,326 @@ enum Commands {
         amount_in: u64,
         #[clap(long)]
         min_amount_out: u64,
+        /// The token_0 account paying the input.
+        #[clap(long)]
+        source_account: String,
+        /// The token_1 account receiving the output.
+        #[clap(long)]
+        destination_account: String,
+        /// Pay the input in a separate SettleSwap instruction at the end of the transaction.
+        #[clap(long)]
+        defer_payment: bool,
+        /// The number of initialized ticks below the price to pass, which bounds how far the
+        /// swap can move the price.
+        #[clap(long, default_value = "8")]
+        max_ticks: usize,
+    },
+    /// Swaps through several pools in a single instruction.
+    SwapRoute {
+        /// The pools to swap through, in order.
+        #[clap(long, required = true, multiple_values = true)]
+        pool_addresses: Vec<String>,
+        #[clap(long)]
+        amount_in: u64,
+        #[clap(long)]
+        min_amount_out: u64,
+        /// The token account paying the first pool's token_0.
+        #[clap(long)]
+        source_account: String,
+        /// The token account receiving the last pool's token_1.
+        #[clap(long)]
+        destination_account: String,
+        /// The number of initialized ticks below the price to pass for each pool.
+        #[clap(long, default_value = "4")]
+        max_ticks: usize,
+    },
+    /// Grows a pool's price oracle so it can hold more observations.
+    IncreaseObservationCardinality {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        observation_cardinality_next: u16,
+    },
+    /// Reads a pool's price oracle at the given number of seconds ago.
+    Observe {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long, required = true, multiple_values = true)]
+        seconds_agos: Vec<u32>,
+    },
+    /// Reads the time, tick and seconds per liquidity accrued inside a tick range.
+    SnapshotCumulativesInside {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        tick_lower: i32,
+        #[clap(long)]
+        tick_upper: i32,
+    },
+    /// Borrows tokens from a pool for the duration of a callback into a borrower program.
+    Flash {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long, default_value = "0")]
+        amount_0: u64,
+        #[clap(long, default_value = "0")]
+        amount_1: u64,
+        /// The token_0 account receiving the loan.
+        #[clap(long)]
+        recipient_0: String,
+        /// The token_1 account receiving the loan.
+        #[clap(long)]
+        recipient_1: String,
+        /// The program called back to use and repay the loan.
+        #[clap(long)]
+        borrower_program: String,
+        /// Additional writable accounts passed through to the borrower program.
+        #[clap(long, multiple_values = true)]
+        callback_accounts: Vec<String>,
+    },
+    /// Places a range order selling token_1 for token_0 one tick spacing below the price.
+    PlaceRangeOrder {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        amount_1: u64,
+        #[clap(long)]
+        tick_lower: i32,
+        /// The token_1 account paying for the order.
+        #[clap(long)]
+        source_account: String,
+    },
+    /// Claims the token_0 of a filled range order.
+    ClaimRangeOrder {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        position_account: String,
+        /// The token_0 account receiving the filled amount.
+        #[clap(long)]
+        destination_account: String,
+    },
+    /// Cancels a range order that has not been filled. What it is worth is then
+    /// paid out by `collect`.
+    CancelRangeOrder {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        position_account: String,
+    },
+    /// Sets up a reward slot of a pool, creating its reward vault.
+    InitializeReward {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        reward_index: u8,
+        #[clap(long)]
+        reward_mint: String,
+    },
+    /// Deposits reward tokens and sets the reward's emission rate.
+    FundReward {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        reward_index: u8,
+        #[clap(long, default_value = "0")]
+        amount: u64,
+        /// The number of reward tokens emitted per second, shared by the in-range liquidity.
+        #[clap(long)]
+        emissions_per_second: f64,
+        /// The admin's reward token account paying the deposit.
+        #[clap(long)]
+        source_account: String,
+    },
+    /// Collects the rewards a position has earned.
+    CollectReward {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        reward_index: u8,
+        #[clap(long)]
+        position_account: String,
+        /// The token account holding the position NFT.
+        #[clap(long)]
+        position_token_account: String,
+        /// The reward token account receiving the rewards.
+        #[clap(long)]
+        destination_account: String,
+    },
+    /// Collects the swap and flash loan fees a position has earned.
+    Collect {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        position_account: String,
+        /// The token account holding the position NFT, for positions with one.
+        #[clap(long)]
+        position_token_account: Option<String>,
+        /// The token_0 account receiving the fees.
+        #[clap(long)]
+        token_0_account: String,
+        /// The token_1 account receiving the fees.
+        #[clap(long)]
+        token_1_account: String,
+    },
+    /// Removes liquidity from a position, leaving the tokens it was worth to be
+    /// paid out by `collect`.
+    RemoveLiquidity {
+        #[clap(long)]
+        pool_address: String,
+        #[clap(long)]
+        position_account: String,
+        /// The token account holding the position NFT.
+        #[clap(long)]
+        position_token_account: String,
+        /// The amount of liquidity to remove, by default all of it.
+        #[clap(long)]
+        amount: Option<u128>,
+    },
+    /// Hands a position without an NFT, such as a range order, over to a new owner.
+    TransferPosition {
+        #[clap(long)]
+        position_account: String,
+        #[clap(long)]
+        new_owner: String,
+    },
+    /// Configures a pool's volatility-based fee; a variable fee control of 0 turns it off.
+    SetDynamicFee {
+        #[clap(long)]
+        pool_address: String,
+        /// Swaps within this many seconds of the previous one keep accumulating volatility.
+        #[clap(long, default_value = "30")]
+        filter_period: u16,
+        /// After this many seconds without a swap, the accumulated volatility is dropped.
+        #[clap(long, default_value = "600")]
+        decay_period: u16,
+        /// The share of the volatility carried over between periods, in basis points.
+        #[clap(long, default_value = "5000")]
+        reduction_factor: u16,
+        #[clap(long)]
+        variable_fee_control: u32,
+        /// The largest volatility, in ten-thousandths of a tick spacing.
+        #[clap(long, default_value = "350000")]
+        max_volatility_accumulator: u32,
+    },
+    /// Closes an empty position, burning its NFT if it has one, and reclaims its rent.
+    ClosePosition {
+        #[clap(long)]
+        position_account: String,
+        /// The token account holding the position NFT, for positions with one.
+        #[clap(long)]
+        position_token_account: Option<String>,
+        /// The account receiving the reclaimed lamports; defaults to the fee payer.
+        #[clap(long)]
+        destination: Option<String>,
+    },
+    /// Closes the account of a tick no position uses any more, returning its
+    /// rent to the account that paid for it.
+    CloseTick {
+        #[clap(long)]
+        tick_account: String,
+    },
+    /// Upgrades an account of the baseline layout to the current one. Pools and
+    /// ticks may only be migrated by the program's upgrade authority, as the
+    /// fee payer: pools are set up with new observations, vaults and tick bitmap
+    /// accounts, and ticks are closed. Anyone may migrate a position, which its
+    /// owner may then close.
+    Migrate {
+        #[clap(long)]
+        account: String,
+        /// One of `pool`, `tick` or `position`.
+        #[clap(long)]
+        account_type: String,
+        /// The number of price observations a migrated pool's oracle can hold.
+        #[clap(long, default_value = "1")]
+        observation_cardinality: usize,
     },
 }
 
+/// Returns the instructions creating the vaults of the pool at `pool`: token
+/// accounts for its two mints owned by its vault authority PDA, whose keypairs
+/// must sign.
+fn create_vaults(
+    client: &RpcClient,
+    fee_payer: &Pubkey,
+    pool: &Pubkey,
+    mints: [&Pubkey; 2],
+    program_id: &Pubkey,
+) -> Result<(Vec<solana_sdk::instruction::Instruction>, Keypair, Keypair), Box<dyn std::error::Error>>
+{
+    let token_0_vault = Keypair::new();
+    let token_1_vault = Keypair::new();
+    let (vault_authority, _) = Pool::find_vault_authority(pool, program_id);
+    let mut instructions = Vec::new();
+    for (vault, mint) in [(&token_0_vault, mints[0]), (&token_1_vault, mints[1])] {
+        let (token_program, vault_len) = token_account_layout(client, mint)?;
+        instructions.push(system_instruction::create_account(
+            fee_payer,
+            &vault.pubkey(),
+            client.get_minimum_balance_for_rent_exemption(vault_len)?,
+            vault_len as u64,
+            &token_program,
+        ));
+        instructions.push(spl_token_2022::instruction::initialize_account3(
+            &token_program,
+            &vault.pubkey(),
+            mint,
+            &vault_authority,
+        )?);
+    }
+    Ok((instructions, token_0_vault, token_1_vault))
+}
+
+/// Returns the token program that owns `mint` and the size of a token account
+/// for it, which grows with the account extensions a Token-2022 mint requires.
+fn token_account_layout(
+    client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<(Pubkey, usize), Box<dyn std::error::Error>> {
+    let account = client.get_account(mint)?;
+    if account.owner != spl_token_2022::id() {
+        return Ok((account.owner, spl_token::state::Account::LEN));
+    }
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)?;
+    let extensions =
+        ExtensionType::get_required_init_account_extensions(&mint_state.get_extension_types()?);
+    let len =
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions)?;
+    Ok((account.owner, len))
+}
+
+/// Returns the token program that owns `mint`.
+fn token_program_of(
+    client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<Pubkey, Box<dyn std::error::Error>> {
+    Ok(client.get_account(mint)?.owner)
+}
+
+/// Returns the accounts of up to `max_ticks` initialized ticks of a pool, in
+/// the order a swap moving its price down would cross them.
+fn crossable_ticks(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    pool: &Pool,
+    max_ticks: usize,
+) -> Result<Vec<Pubkey>, Box<dyn std::error::Error>> {
+    let data = client.get_account_data(&pool.tick_bitmap)?;
+    let words = TickBitmap::words(&data, pool.tick_spacing)?;
+    let mut ticks = Vec::new();
+    let mut tick = pool.current_tick;
+    while ticks.len() < max_ticks && tick > MIN_TICK {
+        let (tick_next, initialized) =
+            tick_bitmap::next_initialized_tick_within_one_word(words, tick, pool.tick_spacing)?;
+        if initialized {
+            ticks.push(Tick::find_address(pool_pubkey, tick_next, program_id).0);
+        }
+        tick = tick_next - 1;
+    }
+    Ok(ticks)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
@@ -71,9 +413,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             token_a_mint,
             token_b_mint,
             initial_price,
+            observation_cardinality,
         } => {
             println!("Initializing pool...");
             let pool_account = Keypair::new();
+            let observations_account = Keypair::new();
             let token_a_mint_pubkey = Pubkey::from_str(&token_a_mint)?;
             let token_b_mint_pubkey = Pubkey::from_str(&token_b_mint)?;
 
@@ -88,7 +432,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &program_id,
             );
 
-            let initialize_instruction = SwapV3Instruction::InitializePool { initial_price };
+            let observations_space = Observations::space(observation_cardinality);
+            let create_observations_instruction = system_instruction::create_account(
+                &fee_payer.pubkey(),
+                &observations_account.pubkey(),
+                client.get_minimum_balance_for_rent_exemption(observations_space)?,
+                observations_space as u64,
+                &program_id,
+            );
+
+            // The program gives new pools a tick spacing of 10.
+            let tick_bitmap_account = Keypair::new();
+            let tick_bitmap_space = TickBitmap::space(10);
+            let create_tick_bitmap_instruction = system_instruction::create_account(
+                &fee_payer.pubkey(),
+                &tick_bitmap_account.pubkey(),
+                client.get_minimum_balance_for_rent_exemption(tick_bitmap_space)?,
+                tick_bitmap_space as u64,
+                &program_id,
+            );
+
+            let (create_vault_instructions, token_0_vault, token_1_vault) = create_vaults(
+                &client,
+                &fee_payer.pubkey(),
+                &pool_account.pubkey(),
+                [&token_a_mint_pubkey, &token_b_mint_pubkey],
+                &program_id,
+            )?;
+
+            let initial_sqrt_price = SqrtPriceX64::from_price_f64(initial_price)
+                .ok_or("initial price cannot be represented as a Q64.64 sqrt price")?;
+            let initialize_instruction = SwapV3Instruction::InitializePool { initial_sqrt_price };
             let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
                 program_id,
                 &initialize_instruction,
@@ -96,107 +470,1010 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     solana_sdk::instruction::AccountMeta::new(pool_account.pubkey(), false),
                     solana_sdk::instruction::AccountMeta::new_readonly(token_a_mint_pubkey, false),
                     solana_sdk::instruction::AccountMeta::new_readonly(token_b_mint_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(observations_account.pubkey(), false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        solana_sdk::sysvar::rent::id(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_0_vault.pubkey(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_1_vault.pubkey(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(tick_bitmap_account.pubkey(), false),
                 ],
             );
 
+            let mut instructions = vec![
+                create_account_instruction,
+                create_observations_instruction,
+                create_tick_bitmap_instruction,
+            ];
+            instructions.extend(create_vault_instructions);
+            instructions.push(instruction);
             let mut transaction =
-                Transaction::new_with_payer(&[create_account_instruction, instruction], Some(&fee_payer.pubkey()));
-            transaction.sign(&[&fee_payer, &pool_account], client.get_latest_blockhash()?);
+                Transaction::new_with_payer(&instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(
+                &[
+                    &fee_payer,
+                    &pool_account,
+                    &observations_account,
+                    &tick_bitmap_account,
+                    &token_0_vault,
+                    &token_1_vault,
+                ],
+                client.get_latest_blockhash()?,
+            );
             client.send_and_confirm_transaction(&transaction)?;
 
             println!("Pool created at address: {}", pool_account.pubkey());
+            println!(
+                "Vaults: token_0 {}, token_1 {}",
+                token_0_vault.pubkey(),
+                token_1_vault.pubkey()
+            );
         }
         Commands::AddLiquidity {
             pool_address,
             amount,
+            amount_0,
+            amount_1,
             tick_lower,
             tick_upper,
+            token_0_account,
+            token_1_account,
         } => {
+            println!("Opening position...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let position_account = Keypair::new();
+            let position_mint = Keypair::new();
+            let position_token_account = Keypair::new();
+            // Tick accounts the pool does not have yet are created by AddLiquidity.
+            let (tick_lower_account, _) = Tick::find_address(&pool_pubkey, tick_lower, &program_id);
+            let (tick_upper_account, _) = Tick::find_address(&pool_pubkey, tick_upper, &program_id);
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let (metadata_account, _) =
+                PositionMetadata::find_address(&position_mint.pubkey(), &program_id);
+
+            // The position NFT's mint can only be minted by the pool's vault authority.
+            let open_position_instructions = vec![
+                system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &position_account.pubkey(),
+                    client.get_minimum_balance_for_rent_exemption(Position::LEN)?,
+                    Position::LEN as u64,
+                    &program_id,
+                ),
+                system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &position_mint.pubkey(),
+                    client.get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?,
+                    spl_token::state::Mint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_mint2(
+                    &spl_token::id(),
+                    &position_mint.pubkey(),
+                    &vault_authority,
+                    None,
+                    0,
+                )?,
+                system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &position_token_account.pubkey(),
+                    client
+                        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?,
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account3(
+                    &spl_token::id(),
+                    &position_token_account.pubkey(),
+                    &position_mint.pubkey(),
+                    &fee_payer.pubkey(),
+                )?,
+                solana_sdk::instruction::Instruction::new_with_borsh(
+                    program_id,
+                    &SwapV3Instruction::OpenPosition {
+                        tick_lower,
+                        tick_upper,
+                    },
+                    vec![
+                        solana_sdk::instruction::AccountMeta::new_readonly(pool_pubkey, false),
+                        solana_sdk::instruction::AccountMeta::new(position_account.pubkey(), false),
+                        solana_sdk::instruction::AccountMeta::new(position_mint.pubkey(), false),
+                        solana_sdk::instruction::AccountMeta::new(
+                            position_token_account.pubkey(),
+                            false,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new(metadata_account, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            fee_payer.pubkey(),
+                            true,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                        solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(spl_token::id(), false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            solana_sdk::system_program::id(),
+                            false,
+                        ),
+                    ],
+                ),
+            ];
+            let mut transaction =
+                Transaction::new_with_payer(&open_position_instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(
+                &[
+                    &fee_payer,
+                    &position_account,
+                    &position_mint,
+                    &position_token_account,
+                ],
+                client.get_latest_blockhash()?,
+            );
+            client.send_and_confirm_transaction(&transaction)?;
+            println!(
+                "Position NFT: mint {}, token account {}",
+                position_mint.pubkey(),
+                position_token_account.pubkey()
+            );
+
             println!("Adding liquidity...");
+
+            let add_liquidity_instruction = match amount {
+                Some(liquidity_amount) => SwapV3Instruction::AddLiquidity {
+                    liquidity_amount,
+                    tick_lower,
+                    tick_upper,
+                },
+                None => SwapV3Instruction::AddLiquidityByAmounts {
+                    amount_0_desired: amount_0.unwrap_or(0),
+                    amount_1_desired: amount_1.unwrap_or(0),
+                    tick_lower,
+                    tick_upper,
+                },
+            };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &add_liquidity_instruction,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(position_account.pubkey(), false),
+                    solana_sdk::instruction::AccountMeta::new(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_upper_account, false),
+                    solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        position_token_account.pubkey(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&token_0_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&token_1_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &pool.token_0_mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &pool.token_1_mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.tick_bitmap, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        solana_sdk::system_program::id(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(metadata_account, false),
+                    solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!(
+                "Liquidity added. Position account: {}",
+                position_account.pubkey()
+            );
+            println!(
+                "Tick accounts: lower {}, upper {}",
+                tick_lower_account, tick_upper_account
+            );
+        }
+        Commands::Swap {
+            pool_address,
+            amount_in,
+            min_amount_out,
+            source_account,
+            destination_account,
+            defer_payment,
+            max_ticks,
+        } => {
+            println!("Swapping tokens...");
             let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let source_pubkey = Pubkey::from_str(&source_account)?;
+            let token_0_program = token_program_of(&client, &pool.token_0_mint)?;
+            let token_1_program = token_program_of(&client, &pool.token_1_mint)?;
+
+            let swap_instruction = SwapV3Instruction::Swap {
+                amount_in,
+                min_amount_out,
+                defer_payment,
+            };
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                solana_sdk::instruction::AccountMeta::new(source_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(&destination_account)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(token_0_program, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(token_1_program, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    solana_sdk::sysvar::instructions::id(),
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.tick_bitmap, false),
+            ];
+            for tick in crossable_ticks(&client, &program_id, &pool_pubkey, &pool, max_ticks)? {
+                accounts.push(solana_sdk::instruction::AccountMeta::new(tick, false));
+            }
+            let mut instructions = vec![solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &swap_instruction,
+                accounts,
+            )];
+            // Other instructions could run between the swap and its settlement.
+            if defer_payment {
+                instructions.push(solana_sdk::instruction::Instruction::new_with_borsh(
+                    program_id,
+                    &SwapV3Instruction::SettleSwap,
+                    vec![
+                        solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            fee_payer.pubkey(),
+                            true,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new(source_pubkey, false),
+                        solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(token_0_program, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            pool.token_0_mint,
+                            false,
+                        ),
+                    ],
+                ));
+            }
+
+            let mut transaction =
+                Transaction::new_with_payer(&instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Swap successful.");
+        }
+        Commands::SwapRoute {
+            pool_addresses,
+            amount_in,
+            min_amount_out,
+            source_account,
+            destination_account,
+            max_ticks,
+        } => {
+            println!("Swapping through {} pools...", pool_addresses.len());
+
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(&source_account)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(&destination_account)?,
+                    false,
+                ),
+            ];
+            let mut tick_counts = Vec::new();
+            let mut previous_output_mint = None;
+            for pool_address in &pool_addresses {
+                let pool_pubkey = Pubkey::from_str(pool_address)?;
+                let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+                // Pools only swap token_0 for token_1, so each must take the
+                // previous pool's token_1 as its token_0.
+                if previous_output_mint.is_some_and(|mint| mint != pool.token_0_mint) {
+                    return Err(format!(
+                        "pool {} does not take the previous pool's token_1 as its token_0",
+                        pool_address
+                    )
+                    .into());
+                }
+                previous_output_mint = Some(pool.token_1_mint);
+                let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+                // The input token's program and mint follow the user's accounts.
+                if tick_counts.is_empty() {
+                    accounts.extend([
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            token_program_of(&client, &pool.token_0_mint)?,
+                            false,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            pool.token_0_mint,
+                            false,
+                        ),
+                    ]);
+                }
+                accounts.extend([
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &pool.token_1_mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.tick_bitmap, false),
+                ]);
+                let ticks = crossable_ticks(&client, &program_id, &pool_pubkey, &pool, max_ticks)?;
+                accounts.extend(
+                    ticks
+                        .iter()
+                        .map(|tick| solana_sdk::instruction::AccountMeta::new(*tick, false)),
+                );
+                tick_counts.push(u8::try_from(ticks.len())?);
+            }
+
+            let route_instruction = SwapV3Instruction::SwapRoute {
+                amount_in,
+                min_amount_out,
+                tick_counts,
+            };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &route_instruction,
+                accounts,
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Route swap successful.");
+        }
+        Commands::IncreaseObservationCardinality {
+            pool_address,
+            observation_cardinality_next,
+        } => {
+            println!("Increasing observation cardinality...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+
+            let increase_instruction = SwapV3Instruction::IncreaseObservationCardinality {
+                observation_cardinality_next,
+            };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &increase_instruction,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        solana_sdk::system_program::id(),
+                        false,
+                    ),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Observation cardinality increased.");
+        }
+        Commands::Observe {
+            pool_address,
+            seconds_agos,
+        } => {
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+
+            let observe_instruction = SwapV3Instruction::Observe {
+                seconds_agos: seconds_agos.clone(),
+            };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &observe_instruction,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.observations, false),
+                ],
+            );
+
+            // The instruction only produces return data, so simulating it is enough.
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            let simulation = client.simulate_transaction(&transaction)?.value;
+            if let Some(err) = simulation.err {
+                return Err(format!("observe failed: {}", err).into());
+            }
+            let return_data = simulation.return_data.ok_or("observe returned no data")?;
+            let result = ObserveResult::try_from_slice(
+                &base64::engine::general_purpose::STANDARD.decode(return_data.data.0)?,
+            )?;
+
+            for (i, seconds_ago) in seconds_agos.iter().enumerate() {
+                println!(
+                    "{}s ago: tick_cumulative = {}, seconds_per_liquidity_cumulative_x64 = {}",
+                    seconds_ago,
+                    result.tick_cumulatives[i],
+                    result.seconds_per_liquidity_cumulative_x64s[i]
+                );
+            }
+        }
+        Commands::SnapshotCumulativesInside {
+            pool_address,
+            tick_lower,
+            tick_upper,
+        } => {
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let (tick_lower_pubkey, _) = Tick::find_address(&pool_pubkey, tick_lower, &program_id);
+            let (tick_upper_pubkey, _) = Tick::find_address(&pool_pubkey, tick_upper, &program_id);
+
+            let snapshot_instruction = SwapV3Instruction::SnapshotCumulativesInside {
+                tick_lower,
+                tick_upper,
+            };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &snapshot_instruction,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(tick_lower_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(tick_upper_pubkey, false),
+                ],
+            );
+
+            // The instruction only produces return data, so simulating it is enough.
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            let simulation = client.simulate_transaction(&transaction)?.value;
+            if let Some(err) = simulation.err {
+                return Err(format!("snapshot failed: {}", err).into());
+            }
+            let return_data = simulation.return_data.ok_or("snapshot returned no data")?;
+            let snapshot = CumulativesInside::try_from_slice(
+                &base64::engine::general_purpose::STANDARD.decode(return_data.data.0)?,
+            )?;
+
+            println!(
+                "tick_cumulative_inside = {}",
+                snapshot.tick_cumulative_inside
+            );
+            println!(
+                "seconds_per_liquidity_inside_x64 = {}",
+                snapshot.seconds_per_liquidity_inside_x64
+            );
+            println!("seconds_inside = {}", snapshot.seconds_inside);
+        }
+        Commands::Flash {
+            pool_address,
+            amount_0,
+            amount_1,
+            recipient_0,
+            recipient_1,
+            borrower_program,
+            callback_accounts,
+        } => {
+            println!("Flash borrowing...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                solana_sdk::instruction::AccountMeta::new(Pubkey::from_str(&recipient_0)?, false),
+                solana_sdk::instruction::AccountMeta::new(Pubkey::from_str(&recipient_1)?, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    token_program_of(&client, &pool.token_0_mint)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    token_program_of(&client, &pool.token_1_mint)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    Pubkey::from_str(&borrower_program)?,
+                    false,
+                ),
+            ];
+            for callback_account in &callback_accounts {
+                accounts.push(solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(callback_account)?,
+                    false,
+                ));
+            }
+            // The fee payer is passed last so the borrower can use it as a signer.
+            accounts.push(solana_sdk::instruction::AccountMeta::new_readonly(
+                fee_payer.pubkey(),
+                true,
+            ));
+
+            let flash_instruction = SwapV3Instruction::Flash { amount_0, amount_1 };
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &flash_instruction,
+                accounts,
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Flash loan repaid.");
+        }
+        Commands::PlaceRangeOrder {
+            pool_address,
+            amount_1,
+            tick_lower,
+            source_account,
+        } => {
+            println!("Placing range order...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
             let position_account = Keypair::new();
-            let tick_lower_account = Keypair::new();
-            let tick_upper_account = Keypair::new();
+            let tick_upper = tick_lower + pool.tick_spacing as i32;
+            let (tick_lower_account, _) = Tick::find_address(&pool_pubkey, tick_lower, &program_id);
+            let (tick_upper_account, _) = Tick::find_address(&pool_pubkey, tick_upper, &program_id);
 
-            let create_position_instruction = system_instruction::create_account(
+            let mut instructions = vec![system_instruction::create_account(
                 &fee_payer.pubkey(),
                 &position_account.pubkey(),
                 client.get_minimum_balance_for_rent_exemption(Position::LEN)?,
                 Position::LEN as u64,
                 &program_id,
+            )];
+            instructions.push(solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::PlaceRangeOrder {
+                    amount_1,
+                    tick_lower,
+                },
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(position_account.pubkey(), false),
+                    solana_sdk::instruction::AccountMeta::new(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_upper_account, false),
+                    solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&source_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &pool.token_1_mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.tick_bitmap, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        solana_sdk::system_program::id(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                ],
+            ));
+
+            let mut transaction =
+                Transaction::new_with_payer(&instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(
+                &[&fee_payer, &position_account],
+                client.get_latest_blockhash()?,
             );
-            let create_tick_lower_instruction = system_instruction::create_account(
-                &fee_payer.pubkey(),
-                &tick_lower_account.pubkey(),
-                client.get_minimum_balance_for_rent_exemption(Tick::LEN)?,
-                Tick::LEN as u64,
-                &program_id,
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!(
+                "Range order placed. Position account: {}",
+                position_account.pubkey()
             );
-            let create_tick_upper_instruction = system_instruction::create_account(
-                &fee_payer.pubkey(),
-                &tick_upper_account.pubkey(),
-                client.get_minimum_balance_for_rent_exemption(Tick::LEN)?,
-                Tick::LEN as u64,
-                &program_id,
+            println!(
+                "Tick accounts: lower {}, upper {}",
+                tick_lower_account, tick_upper_account
             );
+        }
+        Commands::ClaimRangeOrder {
+            pool_address,
+            position_account,
+            destination_account,
+        } => {
+            println!("Claiming range order...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let (tick_lower_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_lower, &program_id);
+            let (tick_upper_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_upper, &program_id);
 
-            let add_liquidity_instruction = SwapV3Instruction::AddLiquidity {
-                liquidity_amount: amount,
-                tick_lower,
-                tick_upper,
-            };
             let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
                 program_id,
-                &add_liquidity_instruction,
+                &SwapV3Instruction::ClaimRangeOrder,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_upper_account, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&destination_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &pool.token_0_mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.tick_bitmap, false),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Range order claimed.");
+        }
+        Commands::CancelRangeOrder {
+            pool_address,
+            position_account,
+        } => {
+            println!("Cancelling range order...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let (tick_lower_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_lower, &program_id);
+            let (tick_upper_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_upper, &program_id);
+
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::CancelRangeOrder,
                 vec![
                     solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
-                    solana_sdk::instruction::AccountMeta::new(position_account.pubkey(), false),
-                    solana_sdk::instruction::AccountMeta::new(tick_lower_account.pubkey(), false),
-                    solana_sdk::instruction::AccountMeta::new(tick_upper_account.pubkey(), false),
+                    solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_upper_account, false),
                     solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.tick_bitmap, false),
                 ],
             );
 
-            let mut transaction = Transaction::new_with_payer(
-                &[
-                    create_position_instruction,
-                    create_tick_lower_instruction,
-                    create_tick_upper_instruction,
-                    instruction,
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Range order cancelled. Use `collect` to withdraw its tokens.");
+        }
+        Commands::InitializeReward {
+            pool_address,
+            reward_index,
+            reward_mint,
+        } => {
+            println!("Initializing reward...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let reward_mint_pubkey = Pubkey::from_str(&reward_mint)?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let reward_vault = Keypair::new();
+            let (token_program, vault_len) = token_account_layout(&client, &reward_mint_pubkey)?;
+
+            let instructions = vec![
+                system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &reward_vault.pubkey(),
+                    client.get_minimum_balance_for_rent_exemption(vault_len)?,
+                    vault_len as u64,
+                    &token_program,
+                ),
+                spl_token_2022::instruction::initialize_account3(
+                    &token_program,
+                    &reward_vault.pubkey(),
+                    &reward_mint_pubkey,
+                    &vault_authority,
+                )?,
+                solana_sdk::instruction::Instruction::new_with_borsh(
+                    program_id,
+                    &SwapV3Instruction::InitializeReward { reward_index },
+                    vec![
+                        solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            fee_payer.pubkey(),
+                            true,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            reward_mint_pubkey,
+                            false,
+                        ),
+                        solana_sdk::instruction::AccountMeta::new_readonly(
+                            reward_vault.pubkey(),
+                            false,
+                        ),
+                    ],
+                ),
+            ];
+
+            let mut transaction =
+                Transaction::new_with_payer(&instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer, &reward_vault], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!(
+                "Reward initialized. Reward vault: {}",
+                reward_vault.pubkey()
+            );
+        }
+        Commands::FundReward {
+            pool_address,
+            reward_index,
+            amount,
+            emissions_per_second,
+            source_account,
+        } => {
+            println!("Funding reward...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let reward = pool
+                .reward_infos
+                .get(reward_index as usize)
+                .ok_or("reward index out of range")?;
+            let emissions_per_second_x64 = (emissions_per_second * 2f64.powi(64)) as u128;
+
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::FundReward {
+                    reward_index,
+                    amount,
+                    emissions_per_second_x64,
+                },
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&source_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(reward.vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &reward.mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(reward.mint, false),
                 ],
-                Some(&fee_payer.pubkey()),
             );
-            transaction.sign(
-                &[
-                    &fee_payer,
-                    &position_account,
-                    &tick_lower_account,
-                    &tick_upper_account,
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Reward funded.");
+        }
+        Commands::CollectReward {
+            pool_address,
+            reward_index,
+            position_account,
+            position_token_account,
+            destination_account,
+        } => {
+            println!("Collecting reward...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let (tick_lower_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_lower, &program_id);
+            let (tick_upper_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_upper, &program_id);
+            let reward = pool
+                .reward_infos
+                .get(reward_index as usize)
+                .ok_or("reward index out of range")?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let (metadata_account, _) =
+                PositionMetadata::find_address(&position.position_mint, &program_id);
+
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::CollectReward { reward_index },
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(tick_upper_account, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&destination_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(reward.vault, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_program_of(&client, &reward.mint)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        Pubkey::from_str(&position_token_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(reward.mint, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(metadata_account, false),
                 ],
-                client.get_latest_blockhash()?,
             );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
             client.send_and_confirm_transaction(&transaction)?;
 
-            println!("Liquidity added. Position account: {}", position_account.pubkey());
+            println!("Reward collected.");
         }
-        Commands::Swap {
+        Commands::Collect {
             pool_address,
-            amount_in,
-            min_amount_out,
+            position_account,
+            position_token_account,
+            token_0_account,
+            token_1_account,
         } => {
-            println!("Swapping tokens...");
+            println!("Collecting fees...");
             let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let (vault_authority, _) = Pool::find_vault_authority(&pool_pubkey, &program_id);
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let (tick_lower_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_lower, &program_id);
+            let (tick_upper_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_upper, &program_id);
 
-            let swap_instruction = SwapV3Instruction::Swap {
-                amount_in,
-                min_amount_out,
-            };
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new_readonly(pool_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(tick_lower_account, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(tick_upper_account, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(&token_0_account)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new(
+                    Pubkey::from_str(&token_1_account)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new(pool.token_0_vault, false),
+                solana_sdk::instruction::AccountMeta::new(pool.token_1_vault, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(vault_authority, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    token_program_of(&client, &pool.token_0_mint)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    token_program_of(&client, &pool.token_1_mint)?,
+                    false,
+                ),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+            ];
+            if position.position_mint != Pubkey::default() {
+                let position_token_account = position_token_account
+                    .ok_or("positions with an NFT need --position-token-account")?;
+                let (metadata_account, _) =
+                    PositionMetadata::find_address(&position.position_mint, &program_id);
+                accounts.push(solana_sdk::instruction::AccountMeta::new_readonly(
+                    Pubkey::from_str(&position_token_account)?,
+                    false,
+                ));
+                accounts.push(solana_sdk::instruction::AccountMeta::new_readonly(
+                    metadata_account,
+                    false,
+                ));
+            }
             let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
                 program_id,
-                &swap_instruction,
+                &SwapV3Instruction::Collect,
+                accounts,
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Fees collected.");
+        }
+        Commands::RemoveLiquidity {
+            pool_address,
+            position_account,
+            position_token_account,
+            amount,
+        } => {
+            println!("Removing liquidity...");
+            let pool_pubkey = Pubkey::from_str(&pool_address)?;
+            let pool = Pool::deserialize(&mut &client.get_account_data(&pool_pubkey)?[..])?;
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let (tick_lower_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_lower, &program_id);
+            let (tick_upper_account, _) =
+                Tick::find_address(&pool_pubkey, position.tick_upper, &program_id);
+            let (metadata_account, _) =
+                PositionMetadata::find_address(&position.position_mint, &program_id);
+
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::RemoveLiquidity {
+                    liquidity_amount: amount.unwrap_or(position.liquidity),
+                },
                 vec![
                     solana_sdk::instruction::AccountMeta::new(pool_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_lower_account, false),
+                    solana_sdk::instruction::AccountMeta::new(tick_upper_account, false),
                     solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                    solana_sdk::instruction::AccountMeta::new(pool.observations, false),
+                    solana_sdk::instruction::AccountMeta::new(pool.tick_bitmap, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        Pubkey::from_str(&position_token_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(metadata_account, false),
                 ],
             );
 
@@ -205,9 +1482,241 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
             client.send_and_confirm_transaction(&transaction)?;
 
-            println!("Swap successful.");
+            println!("Liquidity removed. Collect the tokens with `collect`.");
+        }
+        Commands::TransferPosition {
+            position_account,
+            new_owner,
+        } => {
+            println!("Transferring position...");
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::TransferPosition {
+                    new_owner: Pubkey::from_str(&new_owner)?,
+                },
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&position_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Position transferred.");
+        }
+        Commands::SetDynamicFee {
+            pool_address,
+            filter_period,
+            decay_period,
+            reduction_factor,
+            variable_fee_control,
+            max_volatility_accumulator,
+        } => {
+            println!("Setting dynamic fee...");
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::SetDynamicFee {
+                    filter_period,
+                    decay_period,
+                    reduction_factor,
+                    variable_fee_control,
+                    max_volatility_accumulator,
+                },
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&pool_address)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Dynamic fee set.");
+        }
+        Commands::ClosePosition {
+            position_account,
+            position_token_account,
+            destination,
+        } => {
+            println!("Closing position...");
+            let position_pubkey = Pubkey::from_str(&position_account)?;
+            let position = Position::unpack(&client.get_account_data(&position_pubkey)?)?;
+            let destination_pubkey = match destination {
+                Some(destination) => Pubkey::from_str(&destination)?,
+                None => fee_payer.pubkey(),
+            };
+
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new(position_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new_readonly(fee_payer.pubkey(), true),
+                solana_sdk::instruction::AccountMeta::new(destination_pubkey, false),
+            ];
+            if position.position_mint != Pubkey::default() {
+                let position_token_account = position_token_account
+                    .ok_or("positions with an NFT need --position-token-account")?;
+                let (metadata_address, _) =
+                    PositionMetadata::find_address(&position.position_mint, &program_id);
+                accounts.extend([
+                    solana_sdk::instruction::AccountMeta::new(
+                        Pubkey::from_str(&position_token_account)?,
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(position.position_mint, false),
+                    solana_sdk::instruction::AccountMeta::new(metadata_address, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(spl_token::id(), false),
+                ]);
+            }
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::ClosePosition,
+                accounts,
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Position closed.");
+        }
+        Commands::CloseTick { tick_account } => {
+            println!("Closing tick...");
+            let tick_pubkey = Pubkey::from_str(&tick_account)?;
+            let tick = Tick::unpack(&client.get_account_data(&tick_pubkey)?)?;
+
+            let instruction = solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::CloseTick,
+                vec![
+                    solana_sdk::instruction::AccountMeta::new(tick_pubkey, false),
+                    solana_sdk::instruction::AccountMeta::new(tick.rent_payer, false),
+                ],
+            );
+
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+            transaction.sign(&[&fee_payer], client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Tick closed, rent returned to {}", tick.rent_payer);
+        }
+        Commands::Migrate {
+            account,
+            account_type,
+            observation_cardinality,
+        } => {
+            println!("Migrating account...");
+            let account_pubkey = Pubkey::from_str(&account)?;
+            let account_type = match account_type.as_str() {
+                "pool" => AccountType::Pool,
+                "tick" => AccountType::Tick,
+                "position" => AccountType::Position,
+                _ => return Err(format!("unknown account type: {}", account_type).into()),
+            };
+
+            let mut accounts = vec![
+                solana_sdk::instruction::AccountMeta::new(account_pubkey, false),
+                solana_sdk::instruction::AccountMeta::new(fee_payer.pubkey(), true),
+                solana_sdk::instruction::AccountMeta::new_readonly(
+                    solana_sdk::system_program::id(),
+                    false,
+                ),
+            ];
+            if account_type != AccountType::Position {
+                let (program_data, _) = Pubkey::find_program_address(
+                    &[program_id.as_ref()],
+                    &solana_sdk::bpf_loader_upgradeable::id(),
+                );
+                accounts.push(solana_sdk::instruction::AccountMeta::new_readonly(
+                    program_data,
+                    false,
+                ));
+            }
+            let mut instructions = Vec::new();
+            let observations_account = Keypair::new();
+            let tick_bitmap_account = Keypair::new();
+            let mut vaults = Vec::new();
+            if account_type == AccountType::Pool {
+                let data = client.get_account_data(&account_pubkey)?;
+                if data.len() != Pool::BASELINE_LEN {
+                    return Err("the pool does not have the baseline layout".into());
+                }
+                // The baseline fields are a prefix of the current layout.
+                let mut current = vec![0; Pool::LEN];
+                current[..data.len()].copy_from_slice(&data);
+                let pool = Pool::deserialize(&mut &current[..])?;
+
+                let observations_space = Observations::space(observation_cardinality);
+                instructions.push(system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &observations_account.pubkey(),
+                    client.get_minimum_balance_for_rent_exemption(observations_space)?,
+                    observations_space as u64,
+                    &program_id,
+                ));
+                let tick_bitmap_space = TickBitmap::space(pool.tick_spacing);
+                instructions.push(system_instruction::create_account(
+                    &fee_payer.pubkey(),
+                    &tick_bitmap_account.pubkey(),
+                    client.get_minimum_balance_for_rent_exemption(tick_bitmap_space)?,
+                    tick_bitmap_space as u64,
+                    &program_id,
+                ));
+                let (create_vault_instructions, token_0_vault, token_1_vault) = create_vaults(
+                    &client,
+                    &fee_payer.pubkey(),
+                    &account_pubkey,
+                    [&pool.token_0_mint, &pool.token_1_mint],
+                    &program_id,
+                )?;
+                instructions.extend(create_vault_instructions);
+
+                accounts.extend([
+                    solana_sdk::instruction::AccountMeta::new(observations_account.pubkey(), false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_0_mint, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(pool.token_1_mint, false),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_0_vault.pubkey(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new_readonly(
+                        token_1_vault.pubkey(),
+                        false,
+                    ),
+                    solana_sdk::instruction::AccountMeta::new(tick_bitmap_account.pubkey(), false),
+                ]);
+                vaults.extend([token_0_vault, token_1_vault]);
+            }
+            instructions.push(solana_sdk::instruction::Instruction::new_with_borsh(
+                program_id,
+                &SwapV3Instruction::Migrate { account_type },
+                accounts,
+            ));
+
+            let mut signers = vec![&fee_payer];
+            if account_type == AccountType::Pool {
+                signers.extend([&observations_account, &tick_bitmap_account]);
+                signers.extend(vaults.iter());
+            }
+            let mut transaction =
+                Transaction::new_with_payer(&instructions, Some(&fee_payer.pubkey()));
+            transaction.sign(&signers, client.get_latest_blockhash()?);
+            client.send_and_confirm_transaction(&transaction)?;
+
+            println!("Account migrated.");
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,15 @@
+// The `construct_uint!` expansion trips several style lints we cannot fix here.
+#![allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+
+use uint::construct_uint;
+
+construct_uint! {
+    /// A fixed-width 256-bit unsigned integer, wide enough for the product of two u128 values.
+    pub struct U256(4);
+}
+
+construct_uint! {
+    /// A fixed-width 512-bit unsigned integer, for the few products that involve
+    /// a liquidity shifted into Q64.64 and a second u128 factor.
+    pub struct U512(8);
+}
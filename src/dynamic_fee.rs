@@ -0,0 +1,98 @@
+use crate::{
+    state::Pool,
+    utils::{FEE_RATE_DENOMINATOR, MIN_TICK, get_sqrt_ratio_at_tick},
+};
+use solana_program::program_error::ProgramError;
+
+/// The volatility added by the price moving one tick spacing from the reference tick.
+pub const VOLATILITY_PER_TICK_SPACING: u64 = 10_000;
+
+/// The denominator of `DynamicFee::reduction_factor`.
+pub const REDUCTION_FACTOR_DENOMINATOR: u16 = 10_000;
+
+/// Divides `variable_fee_control` times the squared volatility into units of
+/// `FEE_RATE_DENOMINATOR`. With a control of 10_000, a price ten tick spacings
+/// away from the reference adds a fee of 0.1%.
+pub const VARIABLE_FEE_CONTROL_DENOMINATOR: u128 = 100_000_000_000;
+
+/// The largest total fee of a pool with a variable fee, in units of `FEE_RATE_DENOMINATOR`.
+pub const MAX_FEE: u32 = FEE_RATE_DENOMINATOR / 10;
+
+/// Starts a new volatility period if the previous swap was at least
+/// `filter_period` seconds ago, carrying over a reduced share of the previous
+/// volatility, or none of it after `decay_period` seconds.
+///
+/// Must be called once at the start of every swap, before the price moves.
+///
+/// # Arguments
+/// * `pool` - The pool being swapped in.
+/// * `timestamp` - The current unix timestamp.
+pub fn update_references(pool: &mut Pool, timestamp: i64) {
+    let dynamic_fee = &mut pool.dynamic_fee;
+    if !dynamic_fee.enabled() {
+        return;
+    }
+    let elapsed = timestamp.saturating_sub(dynamic_fee.last_update_timestamp);
+    if elapsed >= dynamic_fee.filter_period as i64 {
+        dynamic_fee.tick_reference = pool.current_tick;
+        dynamic_fee.volatility_reference = if elapsed < dynamic_fee.decay_period as i64 {
+            // The reduction factor is at most the denominator, so this fits in a u32.
+            (dynamic_fee.volatility_accumulator as u64 * dynamic_fee.reduction_factor as u64
+                / REDUCTION_FACTOR_DENOMINATOR as u64) as u32
+        } else {
+            0
+        };
+    }
+    dynamic_fee.last_update_timestamp = timestamp;
+}
+
+/// Measures the volatility at the pool's current tick: the reference volatility
+/// plus the distance from the reference tick, capped at `max_volatility_accumulator`.
+///
+/// Must be called before each swap step, so that the fee follows the price.
+pub fn update_volatility(pool: &mut Pool) {
+    let dynamic_fee = &mut pool.dynamic_fee;
+    if !dynamic_fee.enabled() {
+        return;
+    }
+    let tick_spacing = pool.tick_spacing.max(1) as u64;
+    let ticks_moved = (dynamic_fee.tick_reference as i64 - pool.current_tick as i64).unsigned_abs();
+    let volatility = (dynamic_fee.volatility_reference as u64)
+        .saturating_add(ticks_moved * VOLATILITY_PER_TICK_SPACING / tick_spacing);
+    dynamic_fee.volatility_accumulator =
+        volatility.min(dynamic_fee.max_volatility_accumulator as u64) as u32;
+}
+
+/// Returns the next tick below the price at which the variable fee changes, the
+/// nearest multiple of the tick spacing, so that swap steps can stop there and
+/// pay the fee of each tick spacing they move through. Returns `None` when the
+/// fee cannot change any more, because it is disabled or at its maximum.
+pub fn next_boundary(pool: &Pool) -> Result<Option<i32>, ProgramError> {
+    let dynamic_fee = &pool.dynamic_fee;
+    if !dynamic_fee.enabled()
+        || dynamic_fee.volatility_accumulator >= dynamic_fee.max_volatility_accumulator
+    {
+        return Ok(None);
+    }
+    let tick_spacing = pool.tick_spacing.max(1) as i32;
+    let mut boundary = pool.current_tick.div_euclid(tick_spacing) * tick_spacing;
+    // A price resting on the boundary has already reached it.
+    if get_sqrt_ratio_at_tick(boundary)? >= pool.sqrt_price {
+        boundary -= tick_spacing;
+    }
+    Ok((boundary >= MIN_TICK).then_some(boundary))
+}
+
+/// Returns the fee rate of the next swap step: the pool's base `fee` plus the
+/// variable fee of the current volatility, capped at `MAX_FEE`.
+pub fn fee(pool: &Pool) -> u32 {
+    let dynamic_fee = &pool.dynamic_fee;
+    if !dynamic_fee.enabled() {
+        return pool.fee;
+    }
+    // Both factors are u32s, so the product cannot overflow a u128.
+    let volatility = dynamic_fee.volatility_accumulator as u128;
+    let variable_fee = (dynamic_fee.variable_fee_control as u128 * volatility * volatility)
+        .div_ceil(VARIABLE_FEE_CONTROL_DENOMINATOR);
+    (pool.fee as u128 + variable_fee).min(MAX_FEE.max(pool.fee) as u128) as u32
+}
@@ -19,6 +19,93 @@ pub enum SwapV3Error {
     /// Indicates that an invalid price limit was provided.
     #[error("Invalid price limit")]
     InvalidPriceLimit,
+
+    /// Indicates that a sqrt price lies outside `MIN_SQRT_RATIO..MAX_SQRT_RATIO`.
+    #[error("Invalid sqrt price")]
+    InvalidSqrtPrice,
+
+    /// Indicates that an operation would add or remove zero liquidity.
+    #[error("Zero liquidity")]
+    ZeroLiquidity,
+
+    /// Indicates that a value could not be converted into a Q64.64 fixed-point number.
+    #[error("Invalid fixed-point number")]
+    InvalidFixedPoint,
+
+    /// Indicates that an oracle query reaches further back than the oldest observation.
+    #[error("Observation too old")]
+    ObservationTooOld,
+
+    /// Indicates that a flash loan was not repaid with its fee by the end of the callback.
+    #[error("Flash loan not repaid")]
+    FlashNotRepaid,
+
+    /// Indicates that a pool still has a deferred swap payment waiting to be settled.
+    #[error("Swap debt outstanding")]
+    SwapDebtOutstanding,
+
+    /// Indicates that a deferred-payment swap is not followed by a matching `SettleSwap`.
+    #[error("Missing swap settlement")]
+    SettlementMissing,
+
+    /// Indicates that a hop after the first in a route could not swap all of its input.
+    #[error("Route hop did not use its whole input")]
+    IncompleteRoute,
+
+    /// Indicates that a range order is claimed before the price has crossed it.
+    #[error("Range order not filled")]
+    RangeOrderNotFilled,
+
+    /// Indicates that the signer is not allowed to perform the operation.
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    /// Indicates that a reward index does not refer to a usable reward slot.
+    #[error("Invalid reward index")]
+    InvalidRewardIndex,
+
+    /// Indicates that a reward vault cannot cover a day of the reward's emissions.
+    #[error("Insufficient reward funds")]
+    InsufficientRewardFunds,
+
+    /// Indicates that a Token-2022 mint has an extension pools do not support.
+    #[error("Unsupported mint extension")]
+    UnsupportedMintExtension,
+
+    /// Indicates that a dynamic fee configuration is inconsistent.
+    #[error("Invalid dynamic fee configuration")]
+    InvalidDynamicFeeConfig,
+
+    /// Indicates that a position still has liquidity or owed tokens and cannot be closed.
+    #[error("Position not empty")]
+    PositionNotEmpty,
+
+    /// Indicates that a tick is still used as a boundary by some position and cannot be closed.
+    #[error("Tick in use")]
+    TickInUse,
+
+    /// Indicates that an account has an older layout and must be migrated with `Migrate` first.
+    #[error("Account version outdated")]
+    AccountVersionOutdated,
+
+    /// Indicates that an account has a layout version this program does not know,
+    /// or that `Migrate` has no upgrade from it.
+    #[error("Unsupported account version")]
+    UnsupportedAccountVersion,
+
+    /// Indicates that a range order is cancelled after the price has crossed it,
+    /// and must be claimed with `ClaimRangeOrder` instead.
+    #[error("Range order filled")]
+    RangeOrderFilled,
+
+    /// Indicates that a pool in a route does not take the previous hop's output,
+    /// or the first pool does not take the route's input, as its token_0.
+    #[error("Route pools do not chain token_1 to token_0")]
+    RouteMintMismatch,
+
+    /// Indicates that more liquidity is removed from a position than it holds.
+    #[error("Insufficient liquidity in position")]
+    InsufficientLiquidity,
 }
 
 /// Allows converting a `SwapV3Error` into a `ProgramError`,
@@ -1,4 +1,6 @@
+use crate::{state::AccountType, utils::SqrtPriceX64};
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
 
 /// Defines the instructions that the SwapV3 program can process.
 /// Each variant corresponds to a specific action that can be performed.
@@ -8,22 +10,47 @@ pub enum SwapV3Instruction {
     ///
     /// Accounts expected:
     /// 0. `[writable]` The pool account to initialize.
-    /// 1. `[]` The mint for token_0.
-    /// 2. `[]` The mint for token_1.
-    /// 3. `[]` The rent sysvar.
+    /// 1. `[]` The mint for token_0, of the SPL Token or Token-2022 program.
+    /// 2. `[]` The mint for token_1, of the SPL Token or Token-2022 program.
+    /// 3. `[writable]` The observations account for the pool's price oracle.
+    /// 4. `[]` The rent sysvar.
+    /// 5. `[]` The token_0 vault, a token account owned by the pool's vault authority.
+    /// 6. `[]` The token_1 vault, a token account owned by the pool's vault authority.
+    /// 7. `[signer]` The pool's admin.
+    /// 8. `[writable]` The tick bitmap account, a new account owned by the program of
+    ///    `TickBitmap::space` bytes for the pool's tick spacing of 10.
     InitializePool {
-        /// The initial price of the pool.
-        initial_price: u128,
+        /// The initial sqrt price of the pool.
+        initial_sqrt_price: SqrtPriceX64,
     },
 
-    /// Adds liquidity to a specified range in the pool.
+    /// Adds liquidity to a specified range in the pool. The holder deposits
+    /// the token amounts the liquidity is worth at the current price, rounded
+    /// up; with a transfer fee, enough is sent for the vaults to receive them.
     ///
     /// Accounts expected:
     /// 0. `[writable]` The pool account.
-    /// 1. `[writable]` The position account for the user.
-    /// 2. `[writable]` The tick account for the lower bound.
-    /// 3. `[writable]` The tick account for the upper bound.
-    /// 4. `[signer]` The owner of the position.
+    /// 1. `[writable]` The position account, opened with `OpenPosition` for the same range.
+    /// 2. `[writable]` The tick account for the lower bound, created if it does not
+    ///    exist yet. See `Tick::find_address`.
+    /// 3. `[writable]` The tick account for the upper bound, created if it does not
+    ///    exist yet.
+    /// 4. `[signer]` The holder of the position NFT.
+    /// 5. `[writable]` The pool's observations account.
+    /// 6. `[]` The holder's token account holding the position NFT.
+    /// 7. `[writable]` The holder's token_0 account paying the deposit.
+    /// 8. `[writable]` The holder's token_1 account paying the deposit.
+    /// 9. `[writable]` The pool's token_0 vault.
+    /// 10. `[writable]` The pool's token_1 vault.
+    /// 11. `[]` The token program of token_0.
+    /// 12. `[]` The token program of token_1.
+    /// 13. `[]` The mint for token_0.
+    /// 14. `[]` The mint for token_1.
+    /// 15. `[writable]` The pool's tick bitmap.
+    /// 16. `[]` The system program.
+    /// 17. `[]` The position metadata account, derived from the position mint.
+    /// 18. `[writable, signer]` The funder paying the rent of new tick accounts,
+    ///     which `CloseTick` returns to it. It may be the holder.
     AddLiquidity {
         /// The amount of liquidity to add.
         liquidity_amount: u128,
@@ -33,20 +60,470 @@ pub enum SwapV3Instruction {
         tick_upper: i32,
     },
 
-    /// Swaps one token for another in the pool.
+    /// Adds liquidity to a specified range in the pool, deriving the liquidity
+    /// amount on-chain from the desired token amounts and the current price.
+    ///
+    /// Accounts expected: the same as `AddLiquidity`.
+    AddLiquidityByAmounts {
+        /// The maximum amount of token_0 to deposit.
+        amount_0_desired: u64,
+        /// The maximum amount of token_1 to deposit.
+        amount_1_desired: u64,
+        /// The lower tick boundary for the liquidity.
+        tick_lower: i32,
+        /// The upper tick boundary for the liquidity.
+        tick_upper: i32,
+    },
+
+    /// Swaps one token for another in the pool. The output is sent first; the
+    /// input is then either paid by this instruction or, with `defer_payment`,
+    /// recorded as a debt that a `SettleSwap` instruction for the same pool later
+    /// in the transaction must pay. With a transfer fee on the input token, only
+    /// what reaches the vault is swapped.
     ///
     /// Accounts expected:
     /// 0. `[writable]` The pool account.
     /// 1. `[signer]` The user initiating the swap.
-    /// 2. `[writable]` The user's source token account.
-    /// 3. `[writable]` The user's destination token account.
-    /// 4. `[writable]` The pool's source token vault.
-    /// 5. `[writable]` The pool's destination token vault.
-    /// 6. `[]` The SPL Token program.
+    /// 2. `[writable]` The pool's observations account.
+    /// 3. `[writable]` The user's source token account.
+    /// 4. `[writable]` The user's destination token account.
+    /// 5. `[writable]` The pool's source token vault.
+    /// 6. `[writable]` The pool's destination token vault.
+    /// 7. `[]` The pool's vault authority.
+    /// 8. `[]` The token program of token_0.
+    /// 9. `[]` The token program of token_1.
+    /// 10. `[]` The mint for token_0.
+    /// 11. `[]` The mint for token_1.
+    /// 12. `[]` The Instructions sysvar.
+    /// 13. `[]` The pool's tick bitmap.
+    /// 14. `[writable]` The accounts of the initialized ticks the swap may cross, in
+    ///     order from the current price downwards, with none left out. The swap
+    ///     stops at the first initialized tick whose account is not passed.
     Swap {
         /// The amount of the input token to swap.
         amount_in: u64,
         /// The minimum amount of the output token the user is willing to accept.
         min_amount_out: u64,
+        /// Whether to pay the input in a later `SettleSwap` instruction instead.
+        defer_payment: bool,
+    },
+
+    /// Grows the pool's observations account so the price oracle can keep a
+    /// longer history. The caller pays for the additional rent.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The pool's observations account.
+    /// 2. `[writable, signer]` The payer of the additional rent.
+    /// 3. `[]` The system program.
+    IncreaseObservationCardinality {
+        /// The number of observations the buffer should be able to hold.
+        observation_cardinality_next: u16,
+    },
+
+    /// Reads the price oracle as of each of the given times in the past and
+    /// returns a borsh-serialized `oracle::ObserveResult` as the return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account.
+    /// 1. `[]` The pool's observations account.
+    Observe {
+        /// How many seconds ago to read the oracle at, for each value returned.
+        seconds_agos: Vec<u32>,
+    },
+
+    /// Returns a borsh-serialized `tick::CumulativesInside` snapshot of the time,
+    /// tick and seconds per liquidity accrued inside a tick range as the return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account.
+    /// 1. `[]` The pool's observations account.
+    /// 2. `[]` The tick account for the lower bound. See `Tick::find_address`.
+    /// 3. `[]` The tick account for the upper bound.
+    SnapshotCumulativesInside {
+        /// The lower tick boundary of the range.
+        tick_lower: i32,
+        /// The upper tick boundary of the range.
+        tick_upper: i32,
+    },
+
+    /// Lends tokens from the pool's vaults to a borrower program, which is called
+    /// back with a `FlashCallback` and must repay the amounts plus the pool fee
+    /// before it returns. The fees go to the in-range liquidity providers, who
+    /// take them with `Collect`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The pool's token_0 vault.
+    /// 2. `[writable]` The pool's token_1 vault.
+    /// 3. `[]` The pool's vault authority.
+    /// 4. `[writable]` The token_0 account receiving the loan.
+    /// 5. `[writable]` The token_1 account receiving the loan.
+    /// 6. `[]` The token program of token_0.
+    /// 7. `[]` The token program of token_1.
+    /// 8. `[]` The mint for token_0.
+    /// 9. `[]` The mint for token_1.
+    /// 10. `[]` The borrower program to call back.
+    /// 11. `[]` Any further accounts the borrower program needs, passed through to the callback.
+    Flash {
+        /// The amount of token_0 to borrow.
+        amount_0: u64,
+        /// The amount of token_1 to borrow.
+        amount_1: u64,
+    },
+
+    /// Pays the input owed by a `Swap` with `defer_payment` earlier in the transaction.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[signer]` The payer.
+    /// 2. `[writable]` The payer's token account for the pool's input token.
+    /// 3. `[writable]` The pool's vault for the input token.
+    /// 4. `[]` The token program of the input token.
+    /// 5. `[]` The mint for the input token.
+    SettleSwap,
+
+    /// Swaps through an ordered list of pools, each hop swapping its token_0 for
+    /// token_1 and passing its whole output on as the next hop's input. The
+    /// token_1 of each pool must be the token_0 of the next: a pool listing the
+    /// previous output as its token_1 cannot be swapped through in reverse, and
+    /// the route is rejected with `RouteMintMismatch` before any hop is swapped.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The user initiating the swap.
+    /// 1. `[writable]` The user's token account for the first pool's token_0.
+    /// 2. `[writable]` The user's token account for the last pool's token_1.
+    /// 3. `[]` The token program of the first pool's token_0.
+    /// 4. `[]` The mint for the first pool's token_0.
+    ///
+    /// Then, for each hop in order:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The pool's observations account.
+    /// 2. `[writable]` The pool's token_0 vault.
+    /// 3. `[writable]` The pool's token_1 vault.
+    /// 4. `[]` The pool's vault authority.
+    /// 5. `[]` The token program of the pool's token_1.
+    /// 6. `[]` The mint for the pool's token_1.
+    /// 7. `[]` The pool's tick bitmap.
+    /// 8. `[writable]` The hop's `tick_counts` tick accounts, as for `Swap`.
+    SwapRoute {
+        /// The amount of the first pool's token_0 to swap.
+        amount_in: u64,
+        /// The minimum amount of the last pool's token_1 the user is willing to accept.
+        min_amount_out: u64,
+        /// The number of tick accounts passed for each hop; its length is the number of hops.
+        tick_counts: Vec<u8>,
+    },
+
+    /// Places a range order: a position one tick spacing wide, entirely below
+    /// the current price and so funded only with token_1, which is converted
+    /// into token_0 as swaps move the price down across it. Once the price has
+    /// crossed its lower tick, the order stops providing liquidity and its
+    /// token_0 can be claimed with `ClaimRangeOrder`; until then, it can be
+    /// cancelled with `CancelRangeOrder`. Range orders earn fees while the price
+    /// is inside them, but no rewards.
+    ///
+    /// Orders only sell token_1 for token_0: swaps only take token_0 in, so the
+    /// price only moves down, and an order above the price, selling token_0,
+    /// would never fill. Such orders are rejected with `InvalidTick`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` A new position account for the order.
+    /// 2. `[writable]` The tick account for the lower tick, created if it does not
+    ///    exist yet. See `Tick::find_address`.
+    /// 3. `[writable]` The tick account for the upper tick, created if it does not
+    ///    exist yet.
+    /// 4. `[signer]` The owner of the order.
+    /// 5. `[writable]` The pool's observations account.
+    /// 6. `[writable]` The owner's token account to take token_1 from.
+    /// 7. `[writable]` The pool's token_1 vault.
+    /// 8. `[]` The token program of token_1.
+    /// 9. `[]` The mint for token_1.
+    /// 10. `[writable]` The pool's tick bitmap.
+    /// 11. `[]` The system program.
+    /// 12. `[writable, signer]` The funder paying the rent of new tick accounts,
+    ///     which `CloseTick` returns to it. It may be the owner.
+    PlaceRangeOrder {
+        /// The amount of token_1 to sell.
+        amount_1: u64,
+        /// The lower tick of the order; the upper tick is one tick spacing above it.
+        tick_lower: i32,
+    },
+
+    /// Claims the token_0 of a filled range order, leaving the position without
+    /// liquidity. The fees the order earned are credited to it, for `Collect`.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account.
+    /// 1. `[writable]` The range order's position account.
+    /// 2. `[writable]` The tick account for the lower tick.
+    /// 3. `[writable]` The tick account for the upper tick.
+    /// 4. `[signer]` The owner of the order.
+    /// 5. `[writable]` The owner's token account to receive token_0.
+    /// 6. `[writable]` The pool's token_0 vault.
+    /// 7. `[]` The pool's vault authority.
+    /// 8. `[]` The token program of token_0.
+    /// 9. `[]` The mint for token_0.
+    /// 10. `[writable]` The pool's tick bitmap.
+    ClaimRangeOrder,
+
+    /// Sets up one of the pool's reward slots. Only the pool's admin may do so.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[signer]` The pool's admin.
+    /// 2. `[]` The reward mint, of the SPL Token or Token-2022 program.
+    /// 3. `[]` The reward vault, a token account for the reward mint owned by the
+    ///    pool's vault authority.
+    InitializeReward {
+        /// The index of the reward slot, below `Pool::NUM_REWARDS`.
+        reward_index: u8,
+    },
+
+    /// Deposits reward tokens into a reward vault and sets the reward's
+    /// emission rate. The vault must hold at least a day of emissions afterwards.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[signer]` The pool's admin.
+    /// 2. `[writable]` The admin's token account to take the reward tokens from.
+    /// 3. `[writable]` The reward vault.
+    /// 4. `[]` The token program of the reward mint.
+    /// 5. `[]` The reward mint.
+    FundReward {
+        /// The index of the reward slot.
+        reward_index: u8,
+        /// The amount of reward tokens to deposit.
+        amount: u64,
+        /// The new amount of reward tokens emitted per second, as a Q64.64 number.
+        emissions_per_second_x64: u128,
+    },
+
+    /// Sends a position the rewards it has earned from one of the pool's reward slots.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The position account.
+    /// 2. `[]` The tick account for the position's lower tick.
+    /// 3. `[]` The tick account for the position's upper tick.
+    /// 4. `[signer]` The holder of the position NFT.
+    /// 5. `[writable]` The token account to receive the rewards.
+    /// 6. `[writable]` The reward vault.
+    /// 7. `[]` The pool's vault authority.
+    /// 8. `[]` The token program of the reward mint.
+    /// 9. `[]` The holder's token account holding the position NFT.
+    /// 10. `[]` The reward mint.
+    /// 11. `[]` The position metadata account, derived from the position mint.
+    CollectReward {
+        /// The index of the reward slot.
+        reward_index: u8,
+    },
+
+    /// Opens an empty position and mints the NFT that controls it, along with a
+    /// `PositionMetadata` account describing it. Liquidity is then added with
+    /// `AddLiquidity` by whoever holds the NFT.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account.
+    /// 1. `[writable]` A new position account.
+    /// 2. `[writable]` The position mint: a new mint with 0 decimals, no supply, the
+    ///    pool's vault authority as mint authority and no freeze authority.
+    /// 3. `[writable]` The token account for the position mint to receive the NFT.
+    /// 4. `[writable]` The position metadata account, derived from the position mint.
+    ///    See `PositionMetadata::find_address`.
+    /// 5. `[signer]` The account opening the position.
+    /// 6. `[writable, signer]` The payer of the metadata account's rent.
+    /// 7. `[]` The pool's vault authority.
+    /// 8. `[]` The SPL Token program.
+    /// 9. `[]` The system program.
+    OpenPosition {
+        /// The lower tick boundary of the position.
+        tick_lower: i32,
+        /// The upper tick boundary of the position.
+        tick_upper: i32,
+    },
+
+    /// Hands a position controlled by `Position.owner`, such as a range order,
+    /// over to a new owner. Positions are not derived from their owner, so the
+    /// position keeps its address and everything it has earned moves with it.
+    /// Positions with an NFT are transferred by transferring the NFT instead.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The position account.
+    /// 1. `[signer]` The current owner of the position.
+    TransferPosition {
+        /// The new owner of the position.
+        new_owner: Pubkey,
+    },
+
+    /// Configures the pool's dynamic fee, which adds a fee that grows with the
+    /// recent volatility of the price to every swap step. A `variable_fee_control`
+    /// of zero turns it off. The accumulated volatility is reset. Only the pool's
+    /// admin may do so.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[signer]` The pool's admin.
+    SetDynamicFee {
+        /// Swaps less than this many seconds after the previous one keep its references.
+        filter_period: u16,
+        /// After this many seconds without a swap, the carried-over volatility is
+        /// dropped. Must be longer than `filter_period`.
+        decay_period: u16,
+        /// The share of the volatility carried over to the next period, in basis points.
+        reduction_factor: u16,
+        /// Scales the square of the volatility into the variable fee.
+        variable_fee_control: u32,
+        /// The largest volatility, in ten-thousandths of a tick spacing.
+        max_volatility_accumulator: u32,
+    },
+
+    /// Closes an empty position, sending the lamports of its account to a
+    /// destination of the owner's choosing. The position must have no liquidity
+    /// and nothing owed, so funded positions and unfilled range orders cannot be
+    /// closed. Liquidity is withdrawn with `RemoveLiquidity` and owed tokens with
+    /// `Collect` first. The NFT of a position opened with `OpenPosition` is
+    /// burned and its metadata account closed as well.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The position account.
+    /// 1. `[signer]` The owner of the position, or the holder of its NFT.
+    /// 2. `[writable]` The account to receive the lamports.
+    ///
+    /// For a position with an NFT, also:
+    /// 3. `[writable]` The holder's token account holding the position NFT.
+    /// 4. `[writable]` The position mint.
+    /// 5. `[writable]` The position metadata account.
+    /// 6. `[]` The SPL Token program.
+    ClosePosition,
+
+    /// Closes the account of an uninitialized tick, one that no position uses as
+    /// a boundary, returning its lamports to the account that paid for its rent.
+    /// Anyone may close such a tick, since the lamports can only go to its payer.
+    /// A tick becomes uninitialized once `RemoveLiquidity` and `ClaimRangeOrder`
+    /// have removed all the liquidity using it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The tick account.
+    /// 1. `[writable]` The tick's rent payer.
+    CloseTick,
+
+    /// Upgrades an account of the baseline layout, written before layouts were
+    /// versioned, to the current layout, growing it when the new layout is
+    /// larger. Other instructions reject accounts of an older layout, so they
+    /// must be migrated before they can be used again.
+    ///
+    /// The baseline took no deposits and did not tie ticks and positions to a
+    /// pool, so none of the liquidity it recorded is carried over. A pool keeps
+    /// its tokens, fee and price, and is set up as for `InitializePool`, with
+    /// the program's upgrade authority as its admin. A tick is closed, since
+    /// ticks are now derived from their pool, returning its lamports to the
+    /// upgrade authority. A position keeps its owner and range but belongs to
+    /// no pool, so its owner may only close it with `ClosePosition`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The account to migrate.
+    /// 1. `[writable, signer]` The payer of the rent of the larger layout. For a
+    ///    pool or a tick, the program's upgrade authority.
+    /// 2. `[]` The system program.
+    ///
+    /// For a pool or a tick, also:
+    /// 3. `[]` The program's data account, recording its upgrade authority.
+    ///
+    /// For a pool, also:
+    /// 4. `[writable]` The observations account for the pool's price oracle.
+    /// 5. `[]` The mint for token_0.
+    /// 6. `[]` The mint for token_1.
+    /// 7. `[]` The token_0 vault, a token account owned by the pool's vault authority.
+    /// 8. `[]` The token_1 vault, a token account owned by the pool's vault authority.
+    /// 9. `[writable]` The tick bitmap account, a new account owned by the program of
+    ///    `TickBitmap::space` bytes for the pool's tick spacing.
+    Migrate {
+        /// The kind of the account to migrate.
+        account_type: AccountType,
+    },
+
+    /// Pays out the swap and flash loan fees a position has earned. Fees are
+    /// credited to a position whenever its liquidity changes, when its range
+    /// order is claimed, and by this instruction while it has liquidity.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account.
+    /// 1. `[writable]` The position account.
+    /// 2. `[]` The tick account for the position's lower tick.
+    /// 3. `[]` The tick account for the position's upper tick.
+    /// 4. `[signer]` The owner of the position, or the holder of its NFT.
+    /// 5. `[writable]` The token account to receive the token_0 fees.
+    /// 6. `[writable]` The token account to receive the token_1 fees.
+    /// 7. `[writable]` The pool's token_0 vault.
+    /// 8. `[writable]` The pool's token_1 vault.
+    /// 9. `[]` The pool's vault authority.
+    /// 10. `[]` The token program of token_0.
+    /// 11. `[]` The token program of token_1.
+    /// 12. `[]` The mint for token_0.
+    /// 13. `[]` The mint for token_1.
+    ///
+    /// For a position with an NFT, also:
+    /// 14. `[]` The holder's token account holding the position NFT.
+    /// 15. `[]` The position metadata account, derived from the position mint.
+    Collect,
+
+    /// Removes liquidity from a position opened with `OpenPosition`. The tokens
+    /// the liquidity is worth at the current price, rounded down, are credited
+    /// to the position along with its fees and paid out by `Collect`, after
+    /// which a position without liquidity can be closed with `ClosePosition`.
+    /// Removing more liquidity than the position holds fails with
+    /// `InsufficientLiquidity`. Range orders are withdrawn with `ClaimRangeOrder`
+    /// instead.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The position account.
+    /// 2. `[writable]` The tick account for the position's lower tick.
+    /// 3. `[writable]` The tick account for the position's upper tick.
+    /// 4. `[signer]` The holder of the position NFT.
+    /// 5. `[writable]` The pool's observations account.
+    /// 6. `[writable]` The pool's tick bitmap.
+    /// 7. `[]` The holder's token account holding the position NFT.
+    /// 8. `[]` The position metadata account, derived from the position mint.
+    RemoveLiquidity {
+        /// The amount of liquidity to remove.
+        liquidity_amount: u128,
     },
+
+    /// Cancels a range order that has not been filled, removing its liquidity.
+    /// The tokens it is worth at the current price, rounded down, are credited
+    /// to the position along with its fees and paid out by `Collect`: only
+    /// token_1 while the price is above the order, and some of both while the
+    /// price is inside it. The emptied position can then be closed with
+    /// `ClosePosition`. Filled orders are claimed with `ClaimRangeOrder` instead.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The pool account.
+    /// 1. `[writable]` The range order's position account.
+    /// 2. `[writable]` The tick account for the lower tick.
+    /// 3. `[writable]` The tick account for the upper tick.
+    /// 4. `[signer]` The owner of the order.
+    /// 5. `[writable]` The pool's observations account.
+    /// 6. `[writable]` The pool's tick bitmap.
+    CancelRangeOrder,
+}
+
+/// The instruction data the `Flash` instruction calls the borrower program with.
+///
+/// Accounts passed to the callback:
+/// 0. `[writable]` The pool's token_0 vault, to repay token_0 to.
+/// 1. `[writable]` The pool's token_1 vault, to repay token_1 to.
+/// 2. `[]` Any further accounts passed to `Flash` after the borrower program.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct FlashCallback {
+    /// The amount of token_0 that was lent.
+    pub amount_0: u64,
+    /// The amount of token_1 that was lent.
+    pub amount_1: u64,
+    /// The fee owed in token_0 on top of `amount_0`.
+    pub fee_0: u64,
+    /// The fee owed in token_1 on top of `amount_1`.
+    pub fee_1: u64,
 }
@@ -1,10 +1,17 @@
 #![allow(unexpected_cfgs)]
 
 // Declare the modules that make up the program.
+pub mod big_num;
+pub mod dynamic_fee;
 pub mod error;
 pub mod instruction;
+pub mod oracle;
 pub mod processor;
+pub mod reward;
 pub mod state;
+pub mod tick;
+pub mod tick_bitmap;
+pub mod token;
 pub mod utils;
 
 use solana_program::{
@@ -0,0 +1,314 @@
+use crate::{
+    error::SwapV3Error,
+    state::{Observation, Observations},
+    utils::mul_div_floor,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+
+/// The cumulative values returned by the `Observe` instruction, one entry per
+/// requested `seconds_ago`, in the order they were requested.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq)]
+pub struct ObserveResult {
+    /// The tick cumulative as of each `seconds_ago`.
+    pub tick_cumulatives: Vec<i64>,
+    /// The seconds per liquidity cumulative, as a Q64.64 number, as of each `seconds_ago`.
+    pub seconds_per_liquidity_cumulative_x64s: Vec<u128>,
+}
+
+/// Builds the observation that follows `last`, accumulating the tick and the
+/// seconds per liquidity over the time elapsed since it was written.
+///
+/// # Arguments
+/// * `last` - The most recent observation.
+/// * `timestamp` - The current unix timestamp, not earlier than `last.timestamp`.
+/// * `tick` - The pool's tick during the elapsed period.
+/// * `liquidity` - The pool's in-range liquidity during the elapsed period.
+///
+/// # Returns
+/// The new observation.
+pub fn transform(last: &Observation, timestamp: i64, tick: i32, liquidity: u128) -> Observation {
+    let delta = timestamp.saturating_sub(last.timestamp).max(0);
+
+    // Cumulative values are allowed to wrap; only differences between them are meaningful.
+    Observation {
+        timestamp,
+        tick_cumulative: last
+            .tick_cumulative
+            .wrapping_add((tick as i64).wrapping_mul(delta)),
+        seconds_per_liquidity_cumulative_x64: last
+            .seconds_per_liquidity_cumulative_x64
+            .wrapping_add(((delta as u128) << 64) / liquidity.max(1)),
+        initialized: true,
+    }
+}
+
+/// Writes the first observation of a freshly initialized pool.
+///
+/// # Arguments
+/// * `observations` - The pool's observation buffer, with at least one slot.
+/// * `timestamp` - The current unix timestamp.
+///
+/// # Returns
+/// The observation cardinality, which is the number of slots in the buffer.
+pub fn initialize(observations: &mut Observations, timestamp: i64) -> Result<u16, ProgramError> {
+    let cardinality = u16::try_from(observations.observations.len()).unwrap_or(u16::MAX);
+    let first = observations
+        .observations
+        .first_mut()
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+
+    *first = Observation {
+        timestamp,
+        initialized: true,
+        ..Observation::default()
+    };
+
+    Ok(cardinality)
+}
+
+/// Writes an observation to the buffer, at most once per second.
+///
+/// The tick and liquidity must be the values in effect *before* the current
+/// operation changes them, since they describe the period that has just elapsed.
+/// When the write reaches the end of the slots in use and the buffer has been
+/// grown, the cardinality is bumped to `cardinality_next` instead of wrapping.
+///
+/// # Arguments
+/// * `observations` - The pool's observation buffer.
+/// * `index` - The index of the most recently written observation.
+/// * `cardinality` - The number of observations in use.
+/// * `cardinality_next` - The number of observations the buffer may grow to use.
+/// * `timestamp` - The current unix timestamp.
+/// * `tick` - The pool's current tick.
+/// * `liquidity` - The pool's current in-range liquidity.
+///
+/// # Returns
+/// The new `(index, cardinality)` of the buffer.
+pub fn write(
+    observations: &mut Observations,
+    index: u16,
+    cardinality: u16,
+    cardinality_next: u16,
+    timestamp: i64,
+    tick: i32,
+    liquidity: u128,
+) -> Result<(u16, u16), ProgramError> {
+    let last = *observations
+        .observations
+        .get(index as usize)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    // Only the first write in a given second is recorded.
+    if timestamp <= last.timestamp {
+        return Ok((index, cardinality));
+    }
+
+    // Only start using the new slots once the last slot in use has been written.
+    let cardinality_updated =
+        if cardinality_next > cardinality && index == cardinality.saturating_sub(1) {
+            cardinality_next
+        } else {
+            cardinality
+        };
+    let index_updated = (index + 1) % cardinality_updated.max(1);
+    let slot = observations
+        .observations
+        .get_mut(index_updated as usize)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    *slot = transform(&last, timestamp, tick, liquidity);
+
+    Ok((index_updated, cardinality_updated))
+}
+
+/// Grows the buffer so that it holds `next` observations.
+///
+/// The new slots stay uninitialized until `write` reaches them, so growing the
+/// buffer does not disturb the existing history.
+///
+/// # Arguments
+/// * `observations` - The pool's observation buffer.
+/// * `current` - The current `observation_cardinality_next` of the pool.
+/// * `next` - The requested number of observations.
+///
+/// # Returns
+/// The new `observation_cardinality_next`, which is never lower than `current`.
+pub fn grow(observations: &mut Observations, current: u16, next: u16) -> u16 {
+    if next <= current {
+        return current;
+    }
+    if observations.observations.len() < next as usize {
+        observations
+            .observations
+            .resize(next as usize, Observation::default());
+    }
+    next
+}
+
+/// Finds the observations immediately before and at or after `target`, which
+/// must lie between the oldest observation and `time`.
+///
+/// When `target` is newer than the latest observation, the second observation
+/// is synthesized as of `target` from the pool's current tick and liquidity.
+fn get_surrounding_observations(
+    observations: &Observations,
+    target: i64,
+    tick: i32,
+    index: u16,
+    liquidity: u128,
+    cardinality: u16,
+) -> Result<(Observation, Observation), ProgramError> {
+    let slots = &observations.observations;
+    let cardinality = cardinality.max(1) as usize;
+    let observation_at = |i: usize| {
+        slots
+            .get(i % cardinality)
+            .copied()
+            .ok_or(ProgramError::InvalidAccountData)
+    };
+
+    // Optimistically check whether the target is at or after the newest observation.
+    let newest = observation_at(index as usize)?;
+    if newest.timestamp <= target {
+        if newest.timestamp == target {
+            return Ok((newest, newest));
+        }
+        return Ok((newest, transform(&newest, target, tick, liquidity)));
+    }
+
+    // The oldest observation is the one after the newest, unless the buffer has not
+    // wrapped around yet, in which case it is the first slot.
+    let mut oldest_index = (index as usize + 1) % cardinality;
+    let mut oldest = observation_at(oldest_index)?;
+    if !oldest.initialized {
+        oldest_index = 0;
+        oldest = observation_at(0)?;
+    }
+    if target < oldest.timestamp {
+        return Err(SwapV3Error::ObservationTooOld.into());
+    }
+
+    // Binary search over the initialized slots, ordered from oldest to newest.
+    let mut l = oldest_index;
+    let mut r = index as usize
+        + if index as usize >= oldest_index {
+            0
+        } else {
+            cardinality
+        };
+    loop {
+        let i = (l + r) / 2;
+        let before = observation_at(i)?;
+        if !before.initialized {
+            l = i + 1;
+            continue;
+        }
+        let at_or_after = observation_at(i + 1)?;
+
+        let target_at_or_after = before.timestamp <= target;
+        if target_at_or_after && target <= at_or_after.timestamp {
+            return Ok((before, at_or_after));
+        }
+        if target_at_or_after {
+            l = i + 1;
+        } else {
+            r = i - 1;
+        }
+    }
+}
+
+/// Returns the cumulative values as of `seconds_ago` before `time`.
+///
+/// Values between two observations are linearly interpolated; values after the
+/// newest observation are extrapolated from the pool's current tick and liquidity.
+///
+/// # Arguments
+/// * `observations` - The pool's observation buffer.
+/// * `time` - The current unix timestamp.
+/// * `seconds_ago` - How far back from `time` to look.
+/// * `tick` - The pool's current tick.
+/// * `index` - The index of the most recently written observation.
+/// * `liquidity` - The pool's current in-range liquidity.
+/// * `cardinality` - The number of observations in use.
+///
+/// # Returns
+/// The `(tick_cumulative, seconds_per_liquidity_cumulative_x64)` as of `time - seconds_ago`.
+pub fn observe_single(
+    observations: &Observations,
+    time: i64,
+    seconds_ago: u32,
+    tick: i32,
+    index: u16,
+    liquidity: u128,
+    cardinality: u16,
+) -> Result<(i64, u128), ProgramError> {
+    let target = time - seconds_ago as i64;
+    let (before, after) =
+        get_surrounding_observations(observations, target, tick, index, liquidity, cardinality)?;
+
+    if target == before.timestamp {
+        return Ok((
+            before.tick_cumulative,
+            before.seconds_per_liquidity_cumulative_x64,
+        ));
+    }
+    if target == after.timestamp {
+        return Ok((
+            after.tick_cumulative,
+            after.seconds_per_liquidity_cumulative_x64,
+        ));
+    }
+
+    // The target is strictly between the two observations.
+    let observation_time_delta = after.timestamp - before.timestamp;
+    let target_delta = target - before.timestamp;
+    let tick_cumulative = before.tick_cumulative.wrapping_add(
+        after
+            .tick_cumulative
+            .wrapping_sub(before.tick_cumulative)
+            .wrapping_div(observation_time_delta)
+            .wrapping_mul(target_delta),
+    );
+    let seconds_per_liquidity_cumulative_x64 = before
+        .seconds_per_liquidity_cumulative_x64
+        .wrapping_add(mul_div_floor(
+            after
+                .seconds_per_liquidity_cumulative_x64
+                .wrapping_sub(before.seconds_per_liquidity_cumulative_x64),
+            target_delta as u128,
+            observation_time_delta as u128,
+        )?);
+
+    Ok((tick_cumulative, seconds_per_liquidity_cumulative_x64))
+}
+
+/// Returns the cumulative values as of each of `seconds_agos` before `time`.
+///
+/// See `observe_single` for the meaning of the arguments.
+pub fn observe(
+    observations: &Observations,
+    time: i64,
+    seconds_agos: &[u32],
+    tick: i32,
+    index: u16,
+    liquidity: u128,
+    cardinality: u16,
+) -> Result<ObserveResult, ProgramError> {
+    let mut result = ObserveResult::default();
+    for &seconds_ago in seconds_agos {
+        let (tick_cumulative, seconds_per_liquidity_cumulative_x64) = observe_single(
+            observations,
+            time,
+            seconds_ago,
+            tick,
+            index,
+            liquidity,
+            cardinality,
+        )?;
+        result.tick_cumulatives.push(tick_cumulative);
+        result
+            .seconds_per_liquidity_cumulative_x64s
+            .push(seconds_per_liquidity_cumulative_x64);
+    }
+    Ok(result)
+}
@@ -1,18 +1,48 @@
 use crate::{
+    dynamic_fee,
     error::SwapV3Error,
-    instruction::SwapV3Instruction,
-    state::{Pool, Position, Tick},
+    instruction::{FlashCallback, SwapV3Instruction},
+    oracle, reward,
+    state::{
+        AccountType, DynamicFee, Observation, Observations, Pool, Position, PositionMetadata, Tick,
+        TickBitmap,
+    },
+    tick::{self, GlobalAccumulators},
+    tick_bitmap,
+    token::{self, TokenMint},
+    utils::{
+        FEE_RATE_DENOMINATOR, MIN_SQRT_RATIO, MIN_TICK, Q64, Rounding, SqrtPriceX64,
+        add_liquidity_delta, compute_swap_step, get_amount0_delta, get_amount0_delta_signed,
+        get_amount1_delta, get_amount1_delta_signed, get_liquidity_for_amount1,
+        get_liquidity_for_amounts, get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, mul_div_floor,
+        mul_div_rounding_up,
+    },
 };
 use borsh::BorshDeserialize;
 use borsh::BorshSerialize;
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
+    bpf_loader_upgradeable,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
+    program::{MAX_RETURN_DATA, invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{
+        Sysvar,
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+    },
 };
 
+/// The number of seconds of emissions a reward vault must hold when its rate is set.
+const SECONDS_PER_DAY: u128 = 86_400;
+
 /// The main processor for the SwapV3 program.
 pub struct Processor;
 
@@ -29,9 +59,9 @@ impl Processor {
 
         // Route to the appropriate handler based on the instruction.
         match instruction {
-            SwapV3Instruction::InitializePool { initial_price } => {
+            SwapV3Instruction::InitializePool { initial_sqrt_price } => {
                 msg!("Instruction: InitializePool");
-                Self::process_initialize_pool(accounts, initial_price, program_id)
+                Self::process_initialize_pool(accounts, initial_sqrt_price, program_id)
             }
             SwapV3Instruction::AddLiquidity {
                 liquidity_amount,
@@ -47,12 +77,166 @@ impl Processor {
                     program_id,
                 )
             }
+            SwapV3Instruction::AddLiquidityByAmounts {
+                amount_0_desired,
+                amount_1_desired,
+                tick_lower,
+                tick_upper,
+            } => {
+                msg!("Instruction: AddLiquidityByAmounts");
+                Self::process_add_liquidity_by_amounts(
+                    accounts,
+                    amount_0_desired,
+                    amount_1_desired,
+                    tick_lower,
+                    tick_upper,
+                    program_id,
+                )
+            }
             SwapV3Instruction::Swap {
                 amount_in,
                 min_amount_out,
+                defer_payment,
             } => {
                 msg!("Instruction: Swap");
-                Self::process_swap(accounts, amount_in, min_amount_out, program_id)
+                Self::process_swap(
+                    accounts,
+                    amount_in,
+                    min_amount_out,
+                    defer_payment,
+                    program_id,
+                )
+            }
+            SwapV3Instruction::IncreaseObservationCardinality {
+                observation_cardinality_next,
+            } => {
+                msg!("Instruction: IncreaseObservationCardinality");
+                Self::process_increase_observation_cardinality(
+                    accounts,
+                    observation_cardinality_next,
+                    program_id,
+                )
+            }
+            SwapV3Instruction::Observe { seconds_agos } => {
+                msg!("Instruction: Observe");
+                Self::process_observe(accounts, seconds_agos, program_id)
+            }
+            SwapV3Instruction::SnapshotCumulativesInside {
+                tick_lower,
+                tick_upper,
+            } => {
+                msg!("Instruction: SnapshotCumulativesInside");
+                Self::process_snapshot_cumulatives_inside(
+                    accounts, tick_lower, tick_upper, program_id,
+                )
+            }
+            SwapV3Instruction::Flash { amount_0, amount_1 } => {
+                msg!("Instruction: Flash");
+                Self::process_flash(accounts, amount_0, amount_1, program_id)
+            }
+            SwapV3Instruction::SettleSwap => {
+                msg!("Instruction: SettleSwap");
+                Self::process_settle_swap(accounts, program_id)
+            }
+            SwapV3Instruction::SwapRoute {
+                amount_in,
+                min_amount_out,
+                tick_counts,
+            } => {
+                msg!("Instruction: SwapRoute");
+                Self::process_swap_route(
+                    accounts,
+                    amount_in,
+                    min_amount_out,
+                    tick_counts,
+                    program_id,
+                )
+            }
+            SwapV3Instruction::PlaceRangeOrder {
+                amount_1,
+                tick_lower,
+            } => {
+                msg!("Instruction: PlaceRangeOrder");
+                Self::process_place_range_order(accounts, amount_1, tick_lower, program_id)
+            }
+            SwapV3Instruction::ClaimRangeOrder => {
+                msg!("Instruction: ClaimRangeOrder");
+                Self::process_claim_range_order(accounts, program_id)
+            }
+            SwapV3Instruction::InitializeReward { reward_index } => {
+                msg!("Instruction: InitializeReward");
+                Self::process_initialize_reward(accounts, reward_index, program_id)
+            }
+            SwapV3Instruction::FundReward {
+                reward_index,
+                amount,
+                emissions_per_second_x64,
+            } => {
+                msg!("Instruction: FundReward");
+                Self::process_fund_reward(
+                    accounts,
+                    reward_index,
+                    amount,
+                    emissions_per_second_x64,
+                    program_id,
+                )
+            }
+            SwapV3Instruction::CollectReward { reward_index } => {
+                msg!("Instruction: CollectReward");
+                Self::process_collect_reward(accounts, reward_index, program_id)
+            }
+            SwapV3Instruction::OpenPosition {
+                tick_lower,
+                tick_upper,
+            } => {
+                msg!("Instruction: OpenPosition");
+                Self::process_open_position(accounts, tick_lower, tick_upper, program_id)
+            }
+            SwapV3Instruction::TransferPosition { new_owner } => {
+                msg!("Instruction: TransferPosition");
+                Self::process_transfer_position(accounts, new_owner, program_id)
+            }
+            SwapV3Instruction::SetDynamicFee {
+                filter_period,
+                decay_period,
+                reduction_factor,
+                variable_fee_control,
+                max_volatility_accumulator,
+            } => {
+                msg!("Instruction: SetDynamicFee");
+                let config = DynamicFee {
+                    filter_period,
+                    decay_period,
+                    reduction_factor,
+                    variable_fee_control,
+                    max_volatility_accumulator,
+                    ..DynamicFee::default()
+                };
+                Self::process_set_dynamic_fee(accounts, config, program_id)
+            }
+            SwapV3Instruction::ClosePosition => {
+                msg!("Instruction: ClosePosition");
+                Self::process_close_position(accounts, program_id)
+            }
+            SwapV3Instruction::CloseTick => {
+                msg!("Instruction: CloseTick");
+                Self::process_close_tick(accounts, program_id)
+            }
+            SwapV3Instruction::Migrate { account_type } => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(accounts, account_type, program_id)
+            }
+            SwapV3Instruction::Collect => {
+                msg!("Instruction: Collect");
+                Self::process_collect(accounts, program_id)
+            }
+            SwapV3Instruction::RemoveLiquidity { liquidity_amount } => {
+                msg!("Instruction: RemoveLiquidity");
+                Self::process_remove_liquidity(accounts, liquidity_amount, program_id)
+            }
+            SwapV3Instruction::CancelRangeOrder => {
+                msg!("Instruction: CancelRangeOrder");
+                Self::process_cancel_range_order(accounts, program_id)
             }
         }
     }
@@ -60,29 +244,67 @@ impl Processor {
     /// Processes the InitializePool instruction.
     fn process_initialize_pool(
         accounts: &[AccountInfo],
-        initial_price: u128,
+        initial_sqrt_price: SqrtPriceX64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let pool_account = next_account_info(account_info_iter)?;
         let token_0_mint = next_account_info(account_info_iter)?;
         let token_1_mint = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let _rent_sysvar = next_account_info(account_info_iter)?;
+        let token_0_vault = next_account_info(account_info_iter)?;
+        let token_1_vault = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
 
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
         // Ensure the pool account is owned by the program.
         if pool_account.owner != program_id {
             return Err(ProgramError::IncorrectProgramId);
         }
 
+        // Derive the tick the initial price falls in so that range checks
+        // against `current_tick` are correct from the start. Prices outside
+        // the supported range are rejected with `InvalidSqrtPrice`.
+        let current_tick = get_tick_at_sqrt_ratio(initial_sqrt_price)?;
+
         let mut pool_data = pool_account.try_borrow_mut_data()?;
-        let mut pool = Pool::try_from_slice(&pool_data)?;
+        // The account is allocated with trailing padding, so only read the prefix.
+        let mut pool = Pool::deserialize(&mut &pool_data[..])?;
+        // A pool is initialized only once: initializing it again would make the
+        // caller its admin, in control of its rewards. Pools written before
+        // versions existed also read as version 0.
+        if pool.version != 0 || pool.token_0_mint != Pubkey::default() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        pool.version = Pool::VERSION;
+        pool.discriminator = Pool::DISCRIMINATOR;
 
         pool.token_0_mint = *token_0_mint.key;
         pool.token_1_mint = *token_1_mint.key;
-        pool.sqrt_price_x64 = initial_price;
+        pool.sqrt_price = initial_sqrt_price;
+        pool.current_tick = current_tick;
+        pool.admin = *admin.key;
+
         // Default fee and tick_spacing, can be made configurable
         pool.fee = 500; // 0.05%
         pool.tick_spacing = 10;
 
+        Self::initialize_pool_accounts(
+            &mut pool,
+            pool_account.key,
+            observations_account,
+            token_0_mint,
+            token_1_mint,
+            token_0_vault,
+            token_1_vault,
+            tick_bitmap_account,
+            program_id,
+        )?;
+
         pool.serialize(&mut *pool_data)?;
 
         Ok(())
@@ -94,7 +316,7 @@ impl Processor {
         liquidity_amount: u128,
         tick_lower_idx: i32,
         tick_upper_idx: i32,
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let pool_account = next_account_info(account_info_iter)?;
@@ -102,25 +324,122 @@ impl Processor {
         let tick_lower_account = next_account_info(account_info_iter)?;
         let tick_upper_account = next_account_info(account_info_iter)?;
         let owner = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let position_token_account = next_account_info(account_info_iter)?;
+        let owner_source_0 = next_account_info(account_info_iter)?;
+        let owner_source_1 = next_account_info(account_info_iter)?;
+        let token_0_vault = next_account_info(account_info_iter)?;
+        let token_1_vault = next_account_info(account_info_iter)?;
+        let token_0_program = next_account_info(account_info_iter)?;
+        let token_1_program = next_account_info(account_info_iter)?;
+        let token_0_mint = next_account_info(account_info_iter)?;
+        let token_1_mint = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let metadata_account = next_account_info(account_info_iter)?;
+        let funder = next_account_info(account_info_iter)?;
 
-        if !owner.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        if tick_lower_idx >= tick_upper_idx {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut pool = Pool::try_from_slice(&pool_account.try_borrow_data()?)?;
-        let mut position = Position::try_from_slice(&position_account.try_borrow_data()?)?;
-        let mut tick_lower = Tick::try_from_slice(&tick_lower_account.try_borrow_data()?)?;
-        let mut tick_upper = Tick::try_from_slice(&tick_upper_account.try_borrow_data()?)?;
-
-        position.liquidity += liquidity_amount;
-        tick_lower.liquidity += liquidity_amount as i128;
-        tick_upper.liquidity -= liquidity_amount as i128;
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations
+            || *token_0_vault.key != pool.token_0_vault
+            || *token_1_vault.key != pool.token_1_vault
+            || *tick_bitmap_account.key != pool.tick_bitmap
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+        let mut bitmap_data = tick_bitmap_account.try_borrow_mut_data()?;
+        let bitmap = TickBitmap::words_mut(&mut bitmap_data, pool.tick_spacing)?;
+        let token_0 = TokenMint::load(token_0_program, token_0_mint, &pool.token_0_mint)?;
+        let token_1 = TokenMint::load(token_1_program, token_1_mint, &pool.token_1_mint)?;
 
-        if pool.current_tick >= tick_lower_idx && pool.current_tick < tick_upper_idx {
-            pool.activity_liquidity += liquidity_amount;
+        if position.position_mint == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
         }
+        if position.pool != *pool_account.key
+            || position.tick_lower != tick_lower_idx
+            || position.tick_upper != tick_upper_idx
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_position_holder(
+            &position,
+            position_account.key,
+            Some(pool_account.key),
+            owner,
+            position_token_account,
+            metadata_account,
+            program_id,
+        )?;
+        let mut tick_lower = Self::load_or_create_tick(
+            tick_lower_account,
+            pool_account.key,
+            tick_lower_idx,
+            funder,
+            system_program,
+            program_id,
+        )?;
+        let mut tick_upper = Self::load_or_create_tick(
+            tick_upper_account,
+            pool_account.key,
+            tick_upper_idx,
+            funder,
+            system_program,
+            program_id,
+        )?;
+
+        let liquidity_delta =
+            i128::try_from(liquidity_amount).map_err(|_| SwapV3Error::Overflow)?;
+        Self::modify_position(
+            &mut pool,
+            &mut observations,
+            bitmap,
+            &mut position,
+            &mut tick_lower,
+            &mut tick_upper,
+            liquidity_delta,
+        )?;
+
+        // The deposit is rounded up, so the pool never holds less than the
+        // liquidity is worth. The vaults must receive it after any transfer fee.
+        let (amount_0, amount_1) = Self::amounts_for_liquidity_delta(
+            &pool,
+            tick_lower_idx,
+            tick_upper_idx,
+            liquidity_delta,
+        )?;
+        let amount_0 = u64::try_from(amount_0).map_err(|_| SwapV3Error::Overflow)?;
+        let amount_1 = u64::try_from(amount_1).map_err(|_| SwapV3Error::Overflow)?;
+        token_0.transfer(
+            owner_source_0,
+            token_0_vault,
+            owner,
+            token_0.amount_to_send(amount_0)?,
+            &[],
+        )?;
+        token_1.transfer(
+            owner_source_1,
+            token_1_vault,
+            owner,
+            token_1.amount_to_send(amount_1)?,
+            &[],
+        )?;
+        msg!(
+            "Liquidity added: amount_0 {}, amount_1 {}",
+            amount_0,
+            amount_1
+        );
 
         pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
         position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
         tick_lower.serialize(&mut *tick_lower_account.try_borrow_mut_data()?)?;
         tick_upper.serialize(&mut *tick_upper_account.try_borrow_mut_data()?)?;
@@ -128,40 +447,2430 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes the AddLiquidityByAmounts instruction.
+    fn process_add_liquidity_by_amounts(
+        accounts: &[AccountInfo],
+        amount_0_desired: u64,
+        amount_1_desired: u64,
+        tick_lower_idx: i32,
+        tick_upper_idx: i32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let pool_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+
+        if tick_lower_idx >= tick_upper_idx {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+        let sqrt_lower = get_sqrt_ratio_at_tick(tick_lower_idx)?;
+        let sqrt_upper = get_sqrt_ratio_at_tick(tick_upper_idx)?;
+
+        let liquidity_amount = get_liquidity_for_amounts(
+            pool.sqrt_price,
+            sqrt_lower,
+            sqrt_upper,
+            amount_0_desired as u128,
+            amount_1_desired as u128,
+        )?;
+        if liquidity_amount == 0 {
+            return Err(SwapV3Error::ZeroLiquidity.into());
+        }
+        msg!("Derived liquidity: {}", liquidity_amount);
+
+        Self::process_add_liquidity(
+            accounts,
+            liquidity_amount,
+            tick_lower_idx,
+            tick_upper_idx,
+            program_id,
+        )
+    }
+
     /// Processes the Swap instruction.
     fn process_swap(
         accounts: &[AccountInfo],
         amount_in: u64,
         min_amount_out: u64,
-        _program_id: &Pubkey,
+        defer_payment: bool,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let pool_account = next_account_info(account_info_iter)?;
         let user = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let user_source = next_account_info(account_info_iter)?;
+        let user_destination = next_account_info(account_info_iter)?;
+        let pool_source_vault = next_account_info(account_info_iter)?;
+        let pool_destination_vault = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let token_0_program = next_account_info(account_info_iter)?;
+        let token_1_program = next_account_info(account_info_iter)?;
+        let token_0_mint = next_account_info(account_info_iter)?;
+        let token_1_mint = next_account_info(account_info_iter)?;
+        let instructions_sysvar = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+        let tick_accounts = account_info_iter.as_slice();
+
+        if !user.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations
+            || *pool_source_vault.key != pool.token_0_vault
+            || *pool_destination_vault.key != pool.token_1_vault
+            || *tick_bitmap_account.key != pool.tick_bitmap
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if pool.swap_debt != 0 {
+            return Err(SwapV3Error::SwapDebtOutstanding.into());
+        }
+        let token_0 = TokenMint::load(token_0_program, token_0_mint, &pool.token_0_mint)?;
+        let token_1 = TokenMint::load(token_1_program, token_1_mint, &pool.token_1_mint)?;
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+
+        // Only what reaches the vault after any transfer fee is swapped.
+        let (amount_in_used, amount_out) = Self::execute_swap(
+            &mut pool,
+            pool_account.key,
+            &mut observations,
+            &tick_bitmap_account.try_borrow_data()?,
+            tick_accounts,
+            token_0.amount_received(amount_in)?,
+            program_id,
+        )?;
+
+        if token_1.amount_received(amount_out)? < min_amount_out {
+            return Err(SwapV3Error::InvalidPriceLimit.into());
+        }
+
+        // Send the output first, so that a deferred payment can be funded with it.
+        Self::transfer_from_vault(
+            &pool,
+            pool_account.key,
+            pool_destination_vault,
+            user_destination,
+            vault_authority,
+            &token_1,
+            amount_out,
+        )?;
+
+        if defer_payment {
+            Self::check_settlement_follows(instructions_sysvar, pool_account.key, program_id)?;
+            pool.swap_debt = amount_in_used;
+            msg!("Swap debt recorded: {}", amount_in_used);
+        } else {
+            token_0.transfer(
+                user_source,
+                pool_source_vault,
+                user,
+                token_0.amount_to_send(amount_in_used)?,
+                &[],
+            )?;
+        }
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Swaps `amount_in` of token_0 for token_1 against the pool's liquidity,
+    /// updating the pool, its oracle and any ticks crossed, but moving no tokens.
+    ///
+    /// The next tick to cross is always found in the pool's tick bitmap, and
+    /// `tick_accounts` must hold the accounts of the initialized ticks the swap
+    /// crosses, in the order it crosses them, from the current price downwards.
+    /// When the swap reaches an initialized tick whose account is not passed,
+    /// it stops there and uses only part of `amount_in`.
+    ///
+    /// # Returns
+    /// The amount of token_0 actually used, including fees, and the amount of token_1 out.
+    fn execute_swap(
+        pool: &mut Pool,
+        pool_key: &Pubkey,
+        observations: &mut Observations,
+        tick_bitmap_data: &[u8],
+        mut tick_accounts: &[AccountInfo],
+        amount_in: u64,
+        program_id: &Pubkey,
+    ) -> Result<(u64, u64), ProgramError> {
+        let bitmap = TickBitmap::words(tick_bitmap_data, pool.tick_spacing)?;
+
+        // Record the price before this swap moves it, once per second.
+        let timestamp = Clock::get()?.unix_timestamp;
+        (pool.observation_index, pool.observation_cardinality) = oracle::write(
+            observations,
+            pool.observation_index,
+            pool.observation_cardinality,
+            pool.observation_cardinality_next,
+            timestamp,
+            pool.current_tick,
+            pool.activity_liquidity,
+        )?;
+        reward::update_growths(pool, timestamp)?;
+        dynamic_fee::update_references(pool, timestamp);
+        let mut globals = Self::global_accumulators(pool, observations, timestamp)?;
+
+        // Simplified swap logic: assume token0 is being swapped for token1, so the
+        // price only moves down.
+        let mut amount_remaining = amount_in as u128;
+        let mut amount_out = 0u128;
+
+        while amount_remaining > 0 && pool.sqrt_price > MIN_SQRT_RATIO {
+            // Steps end at the next initialized tick, or at the end of the
+            // bitmap word searched, whichever comes first.
+            let (tick_next, initialized) = tick_bitmap::next_initialized_tick_within_one_word(
+                bitmap,
+                pool.current_tick,
+                pool.tick_spacing,
+            )?;
+            let sqrt_price_tick = get_sqrt_ratio_at_tick(tick_next)?;
+            // With a dynamic fee, steps also end where the fee changes.
+            let sqrt_price_target = match dynamic_fee::next_boundary(pool)? {
+                Some(boundary) => sqrt_price_tick.max(get_sqrt_ratio_at_tick(boundary)?),
+                None => sqrt_price_tick,
+            };
+
+            dynamic_fee::update_volatility(pool);
+            let (sqrt_price_next, amount_in_used, amount_out_step, fee_amount) = compute_swap_step(
+                pool.sqrt_price,
+                sqrt_price_target,
+                pool.activity_liquidity,
+                amount_remaining,
+                true,
+                dynamic_fee::fee(pool),
+            )?;
+            amount_remaining -= amount_in_used + fee_amount;
+            amount_out = amount_out
+                .checked_add(amount_out_step)
+                .ok_or(SwapV3Error::Overflow)?;
+
+            pool.total_fee0 = pool
+                .total_fee0
+                .checked_add(fee_amount)
+                .ok_or(SwapV3Error::Overflow)?;
+            if pool.activity_liquidity > 0 {
+                pool.fee_growth_global_0_x64 = pool
+                    .fee_growth_global_0_x64
+                    .wrapping_add(mul_div_floor(fee_amount, Q64, pool.activity_liquidity)?);
+                globals.fee_growth_global_0_x64 = pool.fee_growth_global_0_x64;
+            }
+            pool.sqrt_price = sqrt_price_next;
+
+            if sqrt_price_next != sqrt_price_tick {
+                pool.current_tick = get_tick_at_sqrt_ratio(sqrt_price_next)?;
+                continue;
+            }
+            if !initialized {
+                pool.current_tick = (tick_next - 1).max(MIN_TICK);
+                continue;
+            }
+
+            // Crossing a tick without its account would leave the in-range
+            // liquidity wrong, so the swap ends at the tick instead.
+            let Some((tick_account, rest)) = tick_accounts.split_first() else {
+                msg!("Swap stopped at tick {}: tick account missing", tick_next);
+                pool.current_tick = tick_next;
+                break;
+            };
+            tick_accounts = rest;
+            let mut tick = Self::load_tick(tick_account, pool_key, tick_next, program_id)?;
+
+            // Moving down across a tick removes the liquidity it adds.
+            let liquidity_net = tick::cross(&mut tick, &globals);
+            pool.activity_liquidity = add_liquidity_delta(
+                pool.activity_liquidity,
+                liquidity_net.checked_neg().ok_or(SwapV3Error::Overflow)?,
+            )?;
+            pool.current_tick = tick_next - 1;
+
+            let filled = tick::fill_range_orders(&mut tick)?;
+            if filled > 0 {
+                msg!("Range orders filled at tick {}: {}", tick.tick, filled);
+            }
+
+            tick.serialize(&mut *tick_account.try_borrow_mut_data()?)?;
+        }
+
+        // The input is bounded by `amount_in` and the output by the pool's
+        // reserves, so both fit in a u64.
+        let amount_in_used = amount_in - amount_remaining as u64;
+        let amount_out = u64::try_from(amount_out).map_err(|_| SwapV3Error::Overflow)?;
+        Ok((amount_in_used, amount_out))
+    }
+
+    /// Processes the SwapRoute instruction.
+    fn process_swap_route(
+        accounts: &[AccountInfo],
+        amount_in: u64,
+        min_amount_out: u64,
+        tick_counts: Vec<u8>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user = next_account_info(account_info_iter)?;
+        let user_source = next_account_info(account_info_iter)?;
+        let user_destination = next_account_info(account_info_iter)?;
+        let input_token_program = next_account_info(account_info_iter)?;
+        let input_mint = next_account_info(account_info_iter)?;
+        let mut hop_accounts = account_info_iter.as_slice();
 
         if !user.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
+        if tick_counts.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Self::check_route_mints(hop_accounts, &tick_counts, input_mint.key, program_id)?;
+
+        // The previous hop's pool, token_1 vault, vault authority and token_1,
+        // whose output is still to be moved on.
+        let mut previous_hop: Option<(Pool, &Pubkey, &AccountInfo, &AccountInfo, TokenMint)> = None;
+        let mut amount = amount_in;
+
+        for &tick_count in &tick_counts {
+            let (hop, rest) = hop_accounts
+                .split_at_checked(8 + tick_count as usize)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            hop_accounts = rest;
+            let [
+                pool_account,
+                observations_account,
+                token_0_vault,
+                token_1_vault,
+                vault_authority,
+                token_1_program,
+                token_1_mint,
+                tick_bitmap_account,
+                tick_accounts @ ..,
+            ] = hop
+            else {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            };
+
+            if pool_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+            if *observations_account.key != pool.observations
+                || *token_0_vault.key != pool.token_0_vault
+                || *token_1_vault.key != pool.token_1_vault
+                || *tick_bitmap_account.key != pool.tick_bitmap
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if pool.swap_debt != 0 {
+                return Err(SwapV3Error::SwapDebtOutstanding.into());
+            }
+            let token_1 = TokenMint::load(token_1_program, token_1_mint, &pool.token_1_mint)?;
+            let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+
+            // Pay this hop's input, from the user for the first hop and from the
+            // previous pool's vault for the others. Only what reaches the vault
+            // after any transfer fee is swapped.
+            match previous_hop.take() {
+                None => {
+                    let token_0 =
+                        TokenMint::load(input_token_program, input_mint, &pool.token_0_mint)?;
+                    let (amount_in_used, amount_out) = Self::execute_swap(
+                        &mut pool,
+                        pool_account.key,
+                        &mut observations,
+                        &tick_bitmap_account.try_borrow_data()?,
+                        tick_accounts,
+                        token_0.amount_received(amount)?,
+                        program_id,
+                    )?;
+                    token_0.transfer(
+                        user_source,
+                        token_0_vault,
+                        user,
+                        token_0.amount_to_send(amount_in_used)?,
+                        &[],
+                    )?;
+                    amount = amount_out;
+                }
+                Some((previous_pool, previous_key, previous_vault, previous_authority, token)) => {
+                    Self::transfer_from_vault(
+                        &previous_pool,
+                        previous_key,
+                        previous_vault,
+                        token_0_vault,
+                        previous_authority,
+                        &token,
+                        amount,
+                    )?;
+                    let amount_received = token.amount_received(amount)?;
+                    let (amount_in_used, amount_out) = Self::execute_swap(
+                        &mut pool,
+                        pool_account.key,
+                        &mut observations,
+                        &tick_bitmap_account.try_borrow_data()?,
+                        tick_accounts,
+                        amount_received,
+                        program_id,
+                    )?;
+                    if amount_in_used != amount_received {
+                        return Err(SwapV3Error::IncompleteRoute.into());
+                    }
+                    amount = amount_out;
+                }
+            }
 
-        let mut pool = Pool::try_from_slice(&pool_account.try_borrow_data()?)?;
+            pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+            observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
 
-        // Simplified swap logic: assume token0 is being swapped for token1
-        // This does not account for concentrated liquidity and is for demonstration only.
-        let amount_in_u128 = amount_in as u128;
-        let price = pool.sqrt_price_x64; // This is sqrt_price_x64
+            previous_hop = Some((
+                pool,
+                pool_account.key,
+                token_1_vault,
+                vault_authority,
+                token_1,
+            ));
+        }
 
-        // Simplified amount_out calculation
-        let amount_out = crate::utils::mul_div(amount_in_u128, price, 1 << 64)?;
+        let (last_pool, last_key, last_vault, last_authority, last_token) =
+            previous_hop.ok_or(ProgramError::InvalidInstructionData)?;
 
-        if amount_out < min_amount_out as u128 {
+        // Only the final output is checked against the user's slippage limit.
+        if last_token.amount_received(amount)? < min_amount_out {
             return Err(SwapV3Error::InvalidPriceLimit.into());
         }
+        msg!("Route output: {}", amount);
+
+        Self::transfer_from_vault(
+            &last_pool,
+            last_key,
+            last_vault,
+            user_destination,
+            last_authority,
+            &last_token,
+            amount,
+        )
+    }
+
+    /// Checks that a route's pools chain up: swaps only take a pool's token_0 in
+    /// and pay its token_1 out, so the first pool's token_0 must be the input
+    /// mint and every other pool's token_0 the token_1 of the pool before it.
+    /// This runs before any hop is swapped, so a misordered route moves no
+    /// tokens.
+    fn check_route_mints(
+        hop_accounts: &[AccountInfo],
+        tick_counts: &[u8],
+        input_mint: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let mut expected_mint = *input_mint;
+        let mut hop_accounts = hop_accounts;
+        for &tick_count in tick_counts {
+            let (hop, rest) = hop_accounts
+                .split_at_checked(8 + tick_count as usize)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            hop_accounts = rest;
+            let pool_account = &hop[0];
+            if pool_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+            if pool.token_0_mint != expected_mint {
+                return Err(SwapV3Error::RouteMintMismatch.into());
+            }
+            expected_mint = pool.token_1_mint;
+        }
+        Ok(())
+    }
+
+    /// Processes the SettleSwap instruction.
+    fn process_settle_swap(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let payer_source = next_account_info(account_info_iter)?;
+        let pool_vault = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let token_mint = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *pool_vault.key != pool.token_0_vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if pool.swap_debt == 0 {
+            msg!("No swap debt to settle");
+            return Ok(());
+        }
+
+        // The debt is what the vault must receive, so any transfer fee comes on top.
+        let token = TokenMint::load(token_program, token_mint, &pool.token_0_mint)?;
+        token.transfer(
+            payer_source,
+            pool_vault,
+            payer,
+            token.amount_to_send(pool.swap_debt)?,
+            &[],
+        )?;
+        msg!("Swap debt settled: {}", pool.swap_debt);
+        pool.swap_debt = 0;
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Ensures that a `SettleSwap` instruction for the pool at `pool_key` comes
+    /// after the currently executing instruction in the transaction.
+    fn check_settlement_follows(
+        instructions_sysvar: &AccountInfo,
+        pool_key: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+        // Loading past the last instruction fails, which ends the search.
+        for index in current_index + 1.. {
+            let Ok(instruction) = load_instruction_at_checked(index, instructions_sysvar) else {
+                break;
+            };
+            let is_settlement = instruction.program_id == *program_id
+                && matches!(
+                    SwapV3Instruction::try_from_slice(&instruction.data),
+                    Ok(SwapV3Instruction::SettleSwap)
+                )
+                && instruction
+                    .accounts
+                    .first()
+                    .is_some_and(|account| account.pubkey == *pool_key);
+            if is_settlement {
+                return Ok(());
+            }
+        }
+        Err(SwapV3Error::SettlementMissing.into())
+    }
+
+    /// Processes the IncreaseObservationCardinality instruction.
+    fn process_increase_observation_cardinality(
+        accounts: &[AccountInfo],
+        observation_cardinality_next: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id || observations_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+
+        let cardinality_next = oracle::grow(
+            &mut observations,
+            pool.observation_cardinality_next,
+            observation_cardinality_next,
+        );
+        if cardinality_next == pool.observation_cardinality_next {
+            msg!("Observation cardinality is already {}", cardinality_next);
+            return Ok(());
+        }
 
-        // Simplified price update
-        pool.sqrt_price_x64 = price + (amount_in_u128 / 1000); // Arbitrary price change
+        let new_len = Observations::space(observations.observations.len());
+        Self::grow_account(observations_account, payer, system_program, new_len)?;
+
+        pool.observation_cardinality_next = cardinality_next;
 
         pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the Observe instruction.
+    fn process_observe(
+        accounts: &[AccountInfo],
+        seconds_agos: Vec<u32>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+
+        let result = oracle::observe(
+            &observations,
+            Clock::get()?.unix_timestamp,
+            &seconds_agos,
+            pool.current_tick,
+            pool.observation_index,
+            pool.activity_liquidity,
+            pool.observation_cardinality,
+        )?;
+
+        // Callers reading through CPI get the values from the return data.
+        let return_data = result.try_to_vec()?;
+        if return_data.len() > MAX_RETURN_DATA {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+
+    /// Processes the SnapshotCumulativesInside instruction.
+    fn process_snapshot_cumulatives_inside(
+        accounts: &[AccountInfo],
+        tick_lower_idx: i32,
+        tick_upper_idx: i32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+        let tick_lower = Self::load_tick(
+            tick_lower_account,
+            pool_account.key,
+            tick_lower_idx,
+            program_id,
+        )?;
+        let tick_upper = Self::load_tick(
+            tick_upper_account,
+            pool_account.key,
+            tick_upper_idx,
+            program_id,
+        )?;
+
+        let globals =
+            Self::global_accumulators(&pool, &observations, Clock::get()?.unix_timestamp)?;
+        let snapshot = tick::snapshot_cumulatives_inside(
+            &tick_lower,
+            &tick_upper,
+            pool.current_tick,
+            &globals,
+        )?;
+
+        // Callers reading through CPI get the values from the return data.
+        set_return_data(&snapshot.try_to_vec()?);
 
         Ok(())
     }
+
+    /// Processes the Flash instruction.
+    fn process_flash(
+        accounts: &[AccountInfo],
+        amount_0: u64,
+        amount_1: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let token_0_vault = next_account_info(account_info_iter)?;
+        let token_1_vault = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let recipient_0 = next_account_info(account_info_iter)?;
+        let recipient_1 = next_account_info(account_info_iter)?;
+        let token_0_program = next_account_info(account_info_iter)?;
+        let token_1_program = next_account_info(account_info_iter)?;
+        let token_0_mint = next_account_info(account_info_iter)?;
+        let token_1_mint = next_account_info(account_info_iter)?;
+        let borrower_program = next_account_info(account_info_iter)?;
+        let callback_accounts = account_info_iter.as_slice();
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *token_0_vault.key != pool.token_0_vault || *token_1_vault.key != pool.token_1_vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_0 = TokenMint::load(token_0_program, token_0_mint, &pool.token_0_mint)?;
+        let token_1 = TokenMint::load(token_1_program, token_1_mint, &pool.token_1_mint)?;
+        if pool.activity_liquidity == 0 {
+            return Err(SwapV3Error::ZeroLiquidity.into());
+        }
+
+        let fee_denominator = FEE_RATE_DENOMINATOR as u128;
+        let fee_0 = mul_div_rounding_up(amount_0 as u128, pool.fee as u128, fee_denominator)?;
+        let fee_1 = mul_div_rounding_up(amount_1 as u128, pool.fee as u128, fee_denominator)?;
+        let fee_0 = u64::try_from(fee_0).map_err(|_| SwapV3Error::Overflow)?;
+        let fee_1 = u64::try_from(fee_1).map_err(|_| SwapV3Error::Overflow)?;
+
+        let balance_0_before = Self::token_balance(token_0_vault)?;
+        let balance_1_before = Self::token_balance(token_1_vault)?;
+
+        Self::transfer_from_vault(
+            &pool,
+            pool_account.key,
+            token_0_vault,
+            recipient_0,
+            vault_authority,
+            &token_0,
+            amount_0,
+        )?;
+        Self::transfer_from_vault(
+            &pool,
+            pool_account.key,
+            token_1_vault,
+            recipient_1,
+            vault_authority,
+            &token_1,
+            amount_1,
+        )?;
+
+        // The borrower cannot re-enter this program from the callback, since the
+        // runtime rejects indirect reentrancy, so the pool state read above stays valid.
+        let mut callback_metas = vec![
+            AccountMeta::new(*token_0_vault.key, false),
+            AccountMeta::new(*token_1_vault.key, false),
+        ];
+        callback_metas.extend(callback_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+        let callback_data = FlashCallback {
+            amount_0,
+            amount_1,
+            fee_0,
+            fee_1,
+        }
+        .try_to_vec()?;
+        let callback =
+            Instruction::new_with_bytes(*borrower_program.key, &callback_data, callback_metas);
+        let mut callback_infos = vec![token_0_vault.clone(), token_1_vault.clone()];
+        callback_infos.extend_from_slice(callback_accounts);
+        callback_infos.push(borrower_program.clone());
+        invoke(&callback, &callback_infos)?;
+
+        // Whatever was repaid beyond the loan itself is the fee, which must cover `Pool.fee`.
+        let paid_0 = Self::token_balance(token_0_vault)?
+            .checked_sub(balance_0_before)
+            .ok_or(SwapV3Error::FlashNotRepaid)?;
+        let paid_1 = Self::token_balance(token_1_vault)?
+            .checked_sub(balance_1_before)
+            .ok_or(SwapV3Error::FlashNotRepaid)?;
+        if paid_0 < fee_0 || paid_1 < fee_1 {
+            return Err(SwapV3Error::FlashNotRepaid.into());
+        }
+        msg!("Flash fees paid: {} token_0, {} token_1", paid_0, paid_1);
+
+        pool.fee_growth_global_0_x64 = pool.fee_growth_global_0_x64.wrapping_add(mul_div_floor(
+            paid_0 as u128,
+            Q64,
+            pool.activity_liquidity,
+        )?);
+        pool.fee_growth_global_1_x64 = pool.fee_growth_global_1_x64.wrapping_add(mul_div_floor(
+            paid_1 as u128,
+            Q64,
+            pool.activity_liquidity,
+        )?);
+        pool.total_fee0 = pool
+            .total_fee0
+            .checked_add(paid_0 as u128)
+            .ok_or(SwapV3Error::Overflow)?;
+        pool.total_fee1 = pool
+            .total_fee1
+            .checked_add(paid_1 as u128)
+            .ok_or(SwapV3Error::Overflow)?;
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the PlaceRangeOrder instruction.
+    fn process_place_range_order(
+        accounts: &[AccountInfo],
+        amount_1: u64,
+        tick_lower_idx: i32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let owner_source = next_account_info(account_info_iter)?;
+        let pool_vault = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let token_mint = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let funder = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations
+            || *pool_vault.key != pool.token_1_vault
+            || *tick_bitmap_account.key != pool.tick_bitmap
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_1 = TokenMint::load(token_program, token_mint, &pool.token_1_mint)?;
+
+        // The order must be one tick spacing wide and entirely below the price,
+        // so that it only holds token_1 until swaps move the price across it.
+        // Swaps only move the price down, so orders above it could never fill.
+        let tick_spacing = pool.tick_spacing as i32;
+        let tick_upper_idx = tick_lower_idx
+            .checked_add(tick_spacing)
+            .ok_or(SwapV3Error::InvalidTick)?;
+        if tick_spacing == 0
+            || tick_lower_idx % tick_spacing != 0
+            || tick_upper_idx > pool.current_tick
+        {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+
+        // The order takes a new position, so that it cannot take over another
+        // one, or what another position is owed.
+        Self::check_new_position(position_account)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        let mut tick_lower = Self::load_or_create_tick(
+            tick_lower_account,
+            pool_account.key,
+            tick_lower_idx,
+            funder,
+            system_program,
+            program_id,
+        )?;
+        let mut tick_upper = Self::load_or_create_tick(
+            tick_upper_account,
+            pool_account.key,
+            tick_upper_idx,
+            funder,
+            system_program,
+            program_id,
+        )?;
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+        let mut bitmap_data = tick_bitmap_account.try_borrow_mut_data()?;
+        let bitmap = TickBitmap::words_mut(&mut bitmap_data, pool.tick_spacing)?;
+
+        let sqrt_lower = get_sqrt_ratio_at_tick(tick_lower_idx)?;
+        let sqrt_upper = get_sqrt_ratio_at_tick(tick_upper_idx)?;
+        let liquidity = get_liquidity_for_amount1(sqrt_lower, sqrt_upper, amount_1 as u128)?;
+        if liquidity == 0 {
+            return Err(SwapV3Error::ZeroLiquidity.into());
+        }
+        let liquidity_delta = i128::try_from(liquidity).map_err(|_| SwapV3Error::Overflow)?;
+        // Rounded up, so that the order can always be filled from its own deposit.
+        let amount_1_required = u64::try_from(get_amount1_delta(
+            sqrt_lower,
+            sqrt_upper,
+            liquidity,
+            Rounding::Up,
+        )?)
+        .map_err(|_| SwapV3Error::Overflow)?;
+
+        position.owner = *owner.key;
+        position.pool = *pool_account.key;
+        position.tick_lower = tick_lower_idx;
+        position.tick_upper = tick_upper_idx;
+        Self::modify_position(
+            &mut pool,
+            &mut observations,
+            bitmap,
+            &mut position,
+            &mut tick_lower,
+            &mut tick_upper,
+            liquidity_delta,
+        )?;
+        tick_lower.range_order_liquidity = tick_lower
+            .range_order_liquidity
+            .checked_add(liquidity)
+            .ok_or(SwapV3Error::Overflow)?;
+        position.range_order = true;
+        position.range_order_epoch = tick_lower.range_order_epoch;
+
+        token_1.transfer(
+            owner_source,
+            pool_vault,
+            owner,
+            token_1.amount_to_send(amount_1_required)?,
+            &[],
+        )?;
+        msg!(
+            "Range order placed: liquidity {}, amount_1 {}",
+            liquidity,
+            amount_1_required
+        );
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+        tick_lower.serialize(&mut *tick_lower_account.try_borrow_mut_data()?)?;
+        tick_upper.serialize(&mut *tick_upper_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the ClaimRangeOrder instruction.
+    fn process_claim_range_order(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let owner_destination = next_account_info(account_info_iter)?;
+        let pool_vault = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let token_mint = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *pool_vault.key != pool.token_0_vault || *tick_bitmap_account.key != pool.tick_bitmap {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_0 = TokenMint::load(token_program, token_mint, &pool.token_0_mint)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.owner != *owner.key {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if !position.range_order || position.pool != *pool_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut tick_lower = Self::load_tick(
+            tick_lower_account,
+            pool_account.key,
+            position.tick_lower,
+            program_id,
+        )?;
+        let mut tick_upper = Self::load_tick(
+            tick_upper_account,
+            pool_account.key,
+            position.tick_upper,
+            program_id,
+        )?;
+        if tick_lower.range_order_epoch == position.range_order_epoch {
+            return Err(SwapV3Error::RangeOrderNotFilled.into());
+        }
+
+        // The ticks still count the order, so the fees it earned while the
+        // price was inside it can be credited; they are taken with `Collect`.
+        tick::update_position_fees(
+            &mut position,
+            tick::fee_growths_inside(&tick_lower, &tick_upper, &pool),
+        )?;
+
+        // The price has crossed the whole range, so the order is entirely in token_0.
+        let amount_0 = u64::try_from(get_amount0_delta(
+            get_sqrt_ratio_at_tick(position.tick_lower)?,
+            get_sqrt_ratio_at_tick(position.tick_upper)?,
+            position.liquidity,
+            Rounding::Down,
+        )?)
+        .map_err(|_| SwapV3Error::Overflow)?;
+        tick::release_range_order(&mut tick_lower, &mut tick_upper, position.liquidity)?;
+        let mut bitmap_data = tick_bitmap_account.try_borrow_mut_data()?;
+        let bitmap = TickBitmap::words_mut(&mut bitmap_data, pool.tick_spacing)?;
+        for tick in [&tick_lower, &tick_upper] {
+            tick_bitmap::set(
+                bitmap,
+                tick.tick,
+                pool.tick_spacing,
+                tick.liquidity_gross != 0,
+            )?;
+        }
+
+        position.liquidity = 0;
+        position.range_order = false;
+
+        Self::transfer_from_vault(
+            &pool,
+            pool_account.key,
+            pool_vault,
+            owner_destination,
+            vault_authority,
+            &token_0,
+            amount_0,
+        )?;
+        msg!("Range order claimed: {}", amount_0);
+
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+        tick_lower.serialize(&mut *tick_lower_account.try_borrow_mut_data()?)?;
+        tick_upper.serialize(&mut *tick_upper_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the CancelRangeOrder instruction.
+    fn process_cancel_range_order(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations
+            || *tick_bitmap_account.key != pool.tick_bitmap
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.owner != *owner.key {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if !position.range_order || position.pool != *pool_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut tick_lower = Self::load_tick(
+            tick_lower_account,
+            pool_account.key,
+            position.tick_lower,
+            program_id,
+        )?;
+        let mut tick_upper = Self::load_tick(
+            tick_upper_account,
+            pool_account.key,
+            position.tick_upper,
+            program_id,
+        )?;
+        if tick_lower.range_order_epoch != position.range_order_epoch {
+            return Err(SwapV3Error::RangeOrderFilled.into());
+        }
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+        let mut bitmap_data = tick_bitmap_account.try_borrow_mut_data()?;
+        let bitmap = TickBitmap::words_mut(&mut bitmap_data, pool.tick_spacing)?;
+
+        // The order is still counted by its lower tick, so that crossing the
+        // tick no longer fills it once it is gone.
+        tick_lower.range_order_liquidity = tick_lower
+            .range_order_liquidity
+            .checked_sub(position.liquidity)
+            .ok_or(SwapV3Error::Overflow)?;
+        let liquidity_delta =
+            -i128::try_from(position.liquidity).map_err(|_| SwapV3Error::Overflow)?;
+        Self::modify_position(
+            &mut pool,
+            &mut observations,
+            bitmap,
+            &mut position,
+            &mut tick_lower,
+            &mut tick_upper,
+            liquidity_delta,
+        )?;
+        position.range_order = false;
+
+        // The withdrawal is rounded down, so the pool never pays out more than
+        // the order is worth.
+        let (amount_0, amount_1) = Self::amounts_for_liquidity_delta(
+            &pool,
+            position.tick_lower,
+            position.tick_upper,
+            liquidity_delta,
+        )?;
+        let amount_0 = u64::try_from(-amount_0).map_err(|_| SwapV3Error::Overflow)?;
+        let amount_1 = u64::try_from(-amount_1).map_err(|_| SwapV3Error::Overflow)?;
+        position.tokens_owed_0 = position
+            .tokens_owed_0
+            .checked_add(amount_0)
+            .ok_or(SwapV3Error::Overflow)?;
+        position.tokens_owed_1 = position
+            .tokens_owed_1
+            .checked_add(amount_1)
+            .ok_or(SwapV3Error::Overflow)?;
+        msg!(
+            "Range order cancelled: amount_0 {}, amount_1 {}",
+            amount_0,
+            amount_1
+        );
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+        tick_lower.serialize(&mut *tick_lower_account.try_borrow_mut_data()?)?;
+        tick_upper.serialize(&mut *tick_upper_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the InitializeReward instruction.
+    fn process_initialize_reward(
+        accounts: &[AccountInfo],
+        reward_index: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let reward_mint = next_account_info(account_info_iter)?;
+        let reward_vault = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        Self::check_admin(&pool, admin)?;
+
+        let reward = pool
+            .reward_infos
+            .get(reward_index as usize)
+            .ok_or(SwapV3Error::InvalidRewardIndex)?;
+        if reward.initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        // Reward tokens must not mix with the pool's reserves.
+        if *reward_vault.key == pool.token_0_vault || *reward_vault.key == pool.token_1_vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (vault_authority, _) = Pool::find_vault_authority(pool_account.key, program_id);
+        Self::check_vault(reward_vault, reward_mint, &vault_authority)?;
+
+        // Accrue the other rewards up to now, so the new one starts from a clean slate.
+        reward::update_growths(&mut pool, Clock::get()?.unix_timestamp)?;
+        let reward = &mut pool.reward_infos[reward_index as usize];
+        reward.mint = *reward_mint.key;
+        reward.vault = *reward_vault.key;
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the FundReward instruction.
+    fn process_fund_reward(
+        accounts: &[AccountInfo],
+        reward_index: u8,
+        amount: u64,
+        emissions_per_second_x64: u128,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let admin_source = next_account_info(account_info_iter)?;
+        let reward_vault = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let reward_mint = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        Self::check_admin(&pool, admin)?;
+
+        let reward = pool
+            .reward_infos
+            .get(reward_index as usize)
+            .filter(|reward| reward.initialized())
+            .ok_or(SwapV3Error::InvalidRewardIndex)?;
+        if *reward_vault.key != reward.vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let token = TokenMint::load(token_program, reward_mint, &reward.mint)?;
+        token.transfer(admin_source, reward_vault, admin, amount, &[])?;
+
+        // Emissions so far accrue at the old rate.
+        reward::update_growths(&mut pool, Clock::get()?.unix_timestamp)?;
+        let emissions_per_day = mul_div_floor(emissions_per_second_x64, SECONDS_PER_DAY, Q64)?;
+        if (Self::token_balance(reward_vault)? as u128) < emissions_per_day {
+            return Err(SwapV3Error::InsufficientRewardFunds.into());
+        }
+        pool.reward_infos[reward_index as usize].emissions_per_second_x64 =
+            emissions_per_second_x64;
+        msg!(
+            "Reward {} funded: {}, emissions per second {}",
+            reward_index,
+            amount,
+            emissions_per_second_x64 >> 64
+        );
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the CollectReward instruction.
+    fn process_collect_reward(
+        accounts: &[AccountInfo],
+        reward_index: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let owner_destination = next_account_info(account_info_iter)?;
+        let reward_vault = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let position_token_account = next_account_info(account_info_iter)?;
+        let reward_mint = next_account_info(account_info_iter)?;
+        let metadata_account = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        let reward = pool
+            .reward_infos
+            .get(reward_index as usize)
+            .filter(|reward| reward.initialized())
+            .ok_or(SwapV3Error::InvalidRewardIndex)?;
+        if *reward_vault.key != reward.vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token = TokenMint::load(token_program, reward_mint, &reward.mint)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.range_order || position.pool != *pool_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_position_holder(
+            &position,
+            position_account.key,
+            Some(pool_account.key),
+            owner,
+            position_token_account,
+            metadata_account,
+            program_id,
+        )?;
+        let tick_lower = Self::load_tick(
+            tick_lower_account,
+            pool_account.key,
+            position.tick_lower,
+            program_id,
+        )?;
+        let tick_upper = Self::load_tick(
+            tick_upper_account,
+            pool_account.key,
+            position.tick_upper,
+            program_id,
+        )?;
+        if tick_lower.liquidity_gross == 0 || tick_upper.liquidity_gross == 0 {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+
+        reward::update_growths(&mut pool, Clock::get()?.unix_timestamp)?;
+        let reward_growths_inside = reward::growths_inside(
+            &tick_lower,
+            &tick_upper,
+            pool.current_tick,
+            &reward::growths_global(&pool),
+        );
+        reward::update_position(&mut position, &reward_growths_inside)?;
+
+        // Pay out as much as the vault holds; the rest stays owed until it is refunded.
+        let owed = &mut position.reward_infos[reward_index as usize].amount_owed;
+        let amount = (*owed).min(Self::token_balance(reward_vault)?);
+        *owed -= amount;
+        Self::transfer_from_vault(
+            &pool,
+            pool_account.key,
+            reward_vault,
+            owner_destination,
+            vault_authority,
+            &token,
+            amount,
+        )?;
+        msg!("Reward {} collected: {}", reward_index, amount);
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the OpenPosition instruction.
+    fn process_open_position(
+        accounts: &[AccountInfo],
+        tick_lower_idx: i32,
+        tick_upper_idx: i32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let position_mint = next_account_info(account_info_iter)?;
+        let recipient = next_account_info(account_info_iter)?;
+        let metadata_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer || !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_account.owner != program_id
+            || position_account.owner != program_id
+            || *position_mint.owner != spl_token::id()
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if tick_lower_idx >= tick_upper_idx {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        // Only multiples of the tick spacing have a bit in the tick bitmap.
+        tick_bitmap::position(tick_lower_idx, pool.tick_spacing)?;
+        tick_bitmap::position(tick_upper_idx, pool.tick_spacing)?;
+        Self::check_new_position(position_account)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+
+        // Only a fresh mint that the pool alone can mint, once, makes a valid NFT.
+        let (authority, _) = Pool::find_vault_authority(pool_account.key, program_id);
+        let mint = spl_token::state::Mint::unpack(&position_mint.try_borrow_data()?)?;
+        if mint.decimals != 0
+            || mint.supply != 0
+            || mint.mint_authority != COption::Some(authority)
+            || mint.freeze_authority.is_some()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (metadata_address, metadata_bump) =
+            PositionMetadata::find_address(position_mint.key, program_id);
+        if *metadata_account.key != metadata_address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                metadata_account.key,
+                Rent::get()?.minimum_balance(PositionMetadata::LEN),
+                PositionMetadata::LEN as u64,
+                program_id,
+            ),
+            &[
+                payer.clone(),
+                metadata_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[
+                PositionMetadata::SEED,
+                position_mint.key.as_ref(),
+                &[metadata_bump],
+            ]],
+        )?;
+        let metadata = PositionMetadata {
+            pool: *pool_account.key,
+            position: *position_account.key,
+            position_mint: *position_mint.key,
+            tick_lower: tick_lower_idx,
+            tick_upper: tick_upper_idx,
+            version: PositionMetadata::VERSION,
+            discriminator: PositionMetadata::DISCRIMINATOR,
+        };
+
+        // Mint the single token, then give up the mint authority so no more can exist.
+        let vault_authority_seeds: &[&[u8]] = &[
+            Pool::VAULT_AUTHORITY_SEED,
+            pool_account.key.as_ref(),
+            &[pool.vault_authority_bump],
+        ];
+        invoke_signed(
+            &spl_token::instruction::mint_to(
+                token_program.key,
+                position_mint.key,
+                recipient.key,
+                vault_authority.key,
+                &[],
+                1,
+            )?,
+            &[
+                position_mint.clone(),
+                recipient.clone(),
+                vault_authority.clone(),
+                token_program.clone(),
+            ],
+            &[vault_authority_seeds],
+        )?;
+        invoke_signed(
+            &spl_token::instruction::set_authority(
+                token_program.key,
+                position_mint.key,
+                None,
+                spl_token::instruction::AuthorityType::MintTokens,
+                vault_authority.key,
+                &[],
+            )?,
+            &[
+                position_mint.clone(),
+                vault_authority.clone(),
+                token_program.clone(),
+            ],
+            &[vault_authority_seeds],
+        )?;
+
+        position.owner = *owner.key;
+        position.pool = *pool_account.key;
+        position.tick_lower = tick_lower_idx;
+        position.tick_upper = tick_upper_idx;
+        position.position_mint = *position_mint.key;
+        msg!("Position NFT minted: {}", position_mint.key);
+
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+        metadata.serialize(&mut *metadata_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the TransferPosition instruction.
+    fn process_transfer_position(
+        accounts: &[AccountInfo],
+        new_owner: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let position_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.owner == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if position.owner != *owner.key {
+            return Err(ProgramError::IllegalOwner);
+        }
+        // The NFT holder controls these positions, so changing the owner would achieve nothing.
+        if position.position_mint != Pubkey::default() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if new_owner == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        position.owner = new_owner;
+        msg!("Position transferred to {}", new_owner);
+
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the ClosePosition instruction.
+    fn process_close_position(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let position_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+
+        if position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if destination.key == position_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.owner == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if position.liquidity != 0
+            || position.range_order
+            || position.tokens_owed_0 != 0
+            || position.tokens_owed_1 != 0
+            || position
+                .reward_infos
+                .iter()
+                .any(|reward| reward.amount_owed != 0)
+        {
+            return Err(SwapV3Error::PositionNotEmpty.into());
+        }
+
+        if position.position_mint == Pubkey::default() {
+            if !owner.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if position.owner != *owner.key {
+                return Err(ProgramError::IllegalOwner);
+            }
+        } else {
+            let holder_token_account = next_account_info(account_info_iter)?;
+            let position_mint = next_account_info(account_info_iter)?;
+            let metadata_account = next_account_info(account_info_iter)?;
+            let token_program = next_account_info(account_info_iter)?;
+
+            // Closing does not involve the pool, so it is not checked.
+            Self::check_position_holder(
+                &position,
+                position_account.key,
+                None,
+                owner,
+                holder_token_account,
+                metadata_account,
+                program_id,
+            )?;
+            if *token_program.key != spl_token::id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if *position_mint.key != position.position_mint {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if destination.key == metadata_account.key {
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            // Burn the NFT, so that no token is left controlling a closed position.
+            invoke(
+                &spl_token::instruction::burn(
+                    token_program.key,
+                    holder_token_account.key,
+                    position_mint.key,
+                    owner.key,
+                    &[],
+                    1,
+                )?,
+                &[
+                    holder_token_account.clone(),
+                    position_mint.clone(),
+                    owner.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+            Self::close_account(metadata_account, destination)?;
+        }
+
+        Self::close_account(position_account, destination)?;
+        msg!("Position closed: {}", position_account.key);
+
+        Ok(())
+    }
+
+    /// Processes the CloseTick instruction.
+    fn process_close_tick(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let tick_account = next_account_info(account_info_iter)?;
+        let rent_payer = next_account_info(account_info_iter)?;
+
+        if tick_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let tick = Tick::unpack(&tick_account.try_borrow_data()?)?;
+        if tick.rent_payer == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if tick.rent_payer != *rent_payer.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // Filled range orders keep their ticks' gross liquidity until claimed.
+        if tick.liquidity_gross != 0 {
+            return Err(SwapV3Error::TickInUse.into());
+        }
+
+        Self::close_account(tick_account, rent_payer)?;
+        msg!("Tick closed: {}", tick.tick);
+
+        Ok(())
+    }
+
+    /// Processes the Migrate instruction.
+    ///
+    /// The baseline layouts are the only ones that shipped before layouts were
+    /// versioned, so accounts are upgraded from them straight to the current
+    /// layout. Since baseline accounts carry no version or discriminator, an
+    /// upgrade only applies to accounts with the exact size of the baseline
+    /// layout of their kind, which no current account has. The baseline took no
+    /// deposits and did not tie ticks and positions to a pool, so none of the
+    /// liquidity it recorded is carried over. Pools and ticks have no owner to
+    /// decide for them, so only the program's upgrade authority may migrate
+    /// them, while positions, which only their owner can then close, may be
+    /// migrated by anyone.
+    fn process_migrate(
+        accounts: &[AccountInfo],
+        account_type: AccountType,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data_len = account.data_len();
+
+        match account_type {
+            // The pool keeps its tokens, fee and price, and is set up like a new
+            // pool around them, with the upgrade authority as its admin.
+            AccountType::Pool if data_len == Pool::BASELINE_LEN => {
+                let program_data = next_account_info(account_info_iter)?;
+                let observations_account = next_account_info(account_info_iter)?;
+                let token_0_mint = next_account_info(account_info_iter)?;
+                let token_1_mint = next_account_info(account_info_iter)?;
+                let token_0_vault = next_account_info(account_info_iter)?;
+                let token_1_vault = next_account_info(account_info_iter)?;
+                let tick_bitmap_account = next_account_info(account_info_iter)?;
+                Self::check_upgrade_authority(program_data, payer, program_id)?;
+
+                let mut pool = Pool::from_baseline(&account.try_borrow_data()?)?;
+                if pool.token_0_mint == Pubkey::default() {
+                    return Err(ProgramError::UninitializedAccount);
+                }
+                if *token_0_mint.key != pool.token_0_mint || *token_1_mint.key != pool.token_1_mint
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                if observations_account.key == account.key {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                pool.admin = *payer.key;
+                Self::initialize_pool_accounts(
+                    &mut pool,
+                    account.key,
+                    observations_account,
+                    token_0_mint,
+                    token_1_mint,
+                    token_0_vault,
+                    token_1_vault,
+                    tick_bitmap_account,
+                    program_id,
+                )?;
+                Self::grow_account(account, payer, system_program, Pool::LEN)?;
+                pool.serialize(&mut *account.try_borrow_mut_data()?)?;
+            }
+            // Ticks are now derived from their pool and created on demand, so a
+            // baseline tick, which held no deposits, is closed instead, its
+            // lamports going to the upgrade authority as its payer is unknown.
+            AccountType::Tick if data_len == Tick::BASELINE_LEN => {
+                let program_data = next_account_info(account_info_iter)?;
+                Self::check_upgrade_authority(program_data, payer, program_id)?;
+                Self::close_account(account, payer)?;
+            }
+            AccountType::Position if data_len == Position::BASELINE_LEN => {
+                let position = Position::from_baseline(&account.try_borrow_data()?)?;
+                Self::grow_account(account, payer, system_program, Position::LEN)?;
+                position.serialize(&mut *account.try_borrow_mut_data()?)?;
+            }
+            _ => return Err(SwapV3Error::UnsupportedAccountVersion.into()),
+        }
+        msg!("Account migrated from the baseline layout");
+
+        Ok(())
+    }
+
+    /// Processes the Collect instruction.
+    fn process_collect(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let owner_token_0 = next_account_info(account_info_iter)?;
+        let owner_token_1 = next_account_info(account_info_iter)?;
+        let token_0_vault = next_account_info(account_info_iter)?;
+        let token_1_vault = next_account_info(account_info_iter)?;
+        let vault_authority = next_account_info(account_info_iter)?;
+        let token_0_program = next_account_info(account_info_iter)?;
+        let token_1_program = next_account_info(account_info_iter)?;
+        let token_0_mint = next_account_info(account_info_iter)?;
+        let token_1_mint = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        if *token_0_vault.key != pool.token_0_vault || *token_1_vault.key != pool.token_1_vault {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_0 = TokenMint::load(token_0_program, token_0_mint, &pool.token_0_mint)?;
+        let token_1 = TokenMint::load(token_1_program, token_1_mint, &pool.token_1_mint)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if position.owner == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if position.pool != *pool_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if position.position_mint == Pubkey::default() {
+            if !owner.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if position.owner != *owner.key {
+                return Err(ProgramError::IllegalOwner);
+            }
+        } else {
+            let holder_token_account = next_account_info(account_info_iter)?;
+            let metadata_account = next_account_info(account_info_iter)?;
+            Self::check_position_holder(
+                &position,
+                position_account.key,
+                Some(pool_account.key),
+                owner,
+                holder_token_account,
+                metadata_account,
+                program_id,
+            )?;
+        }
+
+        // Without liquidity the position earns nothing more, and its ticks may
+        // already be uninitialized.
+        if position.liquidity != 0 {
+            let tick_lower = Self::load_tick(
+                tick_lower_account,
+                pool_account.key,
+                position.tick_lower,
+                program_id,
+            )?;
+            let tick_upper = Self::load_tick(
+                tick_upper_account,
+                pool_account.key,
+                position.tick_upper,
+                program_id,
+            )?;
+            tick::update_position_fees(
+                &mut position,
+                tick::fee_growths_inside(&tick_lower, &tick_upper, &pool),
+            )?;
+        }
+
+        let (amount_0, amount_1) = (position.tokens_owed_0, position.tokens_owed_1);
+        position.tokens_owed_0 = 0;
+        position.tokens_owed_1 = 0;
+        for (vault, destination, token, amount) in [
+            (token_0_vault, owner_token_0, &token_0, amount_0),
+            (token_1_vault, owner_token_1, &token_1, amount_1),
+        ] {
+            if amount > 0 {
+                Self::transfer_from_vault(
+                    &pool,
+                    pool_account.key,
+                    vault,
+                    destination,
+                    vault_authority,
+                    token,
+                    amount,
+                )?;
+            }
+        }
+        msg!("Fees collected: {} token_0, {} token_1", amount_0, amount_1);
+
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the RemoveLiquidity instruction.
+    fn process_remove_liquidity(
+        accounts: &[AccountInfo],
+        liquidity_amount: u128,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let position_account = next_account_info(account_info_iter)?;
+        let tick_lower_account = next_account_info(account_info_iter)?;
+        let tick_upper_account = next_account_info(account_info_iter)?;
+        let owner = next_account_info(account_info_iter)?;
+        let observations_account = next_account_info(account_info_iter)?;
+        let tick_bitmap_account = next_account_info(account_info_iter)?;
+        let position_token_account = next_account_info(account_info_iter)?;
+        let metadata_account = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id || position_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        let mut position = Position::unpack(&position_account.try_borrow_data()?)?;
+        if *observations_account.key != pool.observations
+            || *tick_bitmap_account.key != pool.tick_bitmap
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if position.position_mint == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if position.range_order || position.pool != *pool_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_position_holder(
+            &position,
+            position_account.key,
+            Some(pool_account.key),
+            owner,
+            position_token_account,
+            metadata_account,
+            program_id,
+        )?;
+        if liquidity_amount == 0 {
+            return Err(SwapV3Error::ZeroLiquidity.into());
+        }
+        if liquidity_amount > position.liquidity {
+            return Err(SwapV3Error::InsufficientLiquidity.into());
+        }
+        let mut observations = Observations::unpack(&observations_account.try_borrow_data()?)?;
+        let mut bitmap_data = tick_bitmap_account.try_borrow_mut_data()?;
+        let bitmap = TickBitmap::words_mut(&mut bitmap_data, pool.tick_spacing)?;
+        let mut tick_lower = Self::load_tick(
+            tick_lower_account,
+            pool_account.key,
+            position.tick_lower,
+            program_id,
+        )?;
+        let mut tick_upper = Self::load_tick(
+            tick_upper_account,
+            pool_account.key,
+            position.tick_upper,
+            program_id,
+        )?;
+
+        let liquidity_delta =
+            -i128::try_from(liquidity_amount).map_err(|_| SwapV3Error::Overflow)?;
+        Self::modify_position(
+            &mut pool,
+            &mut observations,
+            bitmap,
+            &mut position,
+            &mut tick_lower,
+            &mut tick_upper,
+            liquidity_delta,
+        )?;
+
+        // The withdrawal is rounded down, so the pool never pays out more than
+        // the liquidity is worth.
+        let (amount_0, amount_1) = Self::amounts_for_liquidity_delta(
+            &pool,
+            position.tick_lower,
+            position.tick_upper,
+            liquidity_delta,
+        )?;
+        let amount_0 = u64::try_from(-amount_0).map_err(|_| SwapV3Error::Overflow)?;
+        let amount_1 = u64::try_from(-amount_1).map_err(|_| SwapV3Error::Overflow)?;
+        position.tokens_owed_0 = position
+            .tokens_owed_0
+            .checked_add(amount_0)
+            .ok_or(SwapV3Error::Overflow)?;
+        position.tokens_owed_1 = position
+            .tokens_owed_1
+            .checked_add(amount_1)
+            .ok_or(SwapV3Error::Overflow)?;
+        msg!(
+            "Liquidity removed: amount_0 {}, amount_1 {}",
+            amount_0,
+            amount_1
+        );
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+        position.serialize(&mut *position_account.try_borrow_mut_data()?)?;
+        tick_lower.serialize(&mut *tick_lower_account.try_borrow_mut_data()?)?;
+        tick_upper.serialize(&mut *tick_upper_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Processes the SetDynamicFee instruction.
+    fn process_set_dynamic_fee(
+        accounts: &[AccountInfo],
+        config: DynamicFee,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        if pool_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool = Pool::unpack(&pool_account.try_borrow_data()?)?;
+        Self::check_admin(&pool, admin)?;
+
+        if config.enabled()
+            && (config.filter_period >= config.decay_period
+                || config.reduction_factor > dynamic_fee::REDUCTION_FACTOR_DENOMINATOR)
+        {
+            return Err(SwapV3Error::InvalidDynamicFeeConfig.into());
+        }
+
+        // Start measuring from the current price, without any earlier volatility.
+        pool.dynamic_fee = DynamicFee {
+            tick_reference: pool.current_tick,
+            last_update_timestamp: Clock::get()?.unix_timestamp,
+            ..config
+        };
+        msg!(
+            "Dynamic fee set: variable fee control {}",
+            config.variable_fee_control
+        );
+
+        pool.serialize(&mut *pool_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Ensures that `position_account` is a new, rent-exempt position account
+    /// that the program has not written yet, so that opening a position cannot
+    /// take over another position, or any other account the program owns.
+    fn check_new_position(position_account: &AccountInfo) -> ProgramResult {
+        if position_account.data_len() != Position::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !Position::is_new(&position_account.try_borrow_data()?) {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if !Rent::get()?.is_exempt(position_account.lamports(), Position::LEN) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        Ok(())
+    }
+
+    /// Ensures that `holder` signed the transaction and holds the NFT of
+    /// `position` in `holder_token_account`, and that the NFT's metadata
+    /// account describes this position of the pool at `pool_key`. Closing a
+    /// position does not involve its pool, so no pool is given then.
+    #[allow(clippy::too_many_arguments)]
+    fn check_position_holder(
+        position: &Position,
+        position_key: &Pubkey,
+        pool_key: Option<&Pubkey>,
+        holder: &AccountInfo,
+        holder_token_account: &AccountInfo,
+        metadata_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !holder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *holder_token_account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_account =
+            spl_token::state::Account::unpack(&holder_token_account.try_borrow_data()?)?;
+        if position.position_mint == Pubkey::default()
+            || token_account.mint != position.position_mint
+            || token_account.owner != *holder.key
+            || token_account.amount != 1
+        {
+            return Err(SwapV3Error::Unauthorized.into());
+        }
+
+        if metadata_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (metadata_address, _) =
+            PositionMetadata::find_address(&position.position_mint, program_id);
+        if *metadata_account.key != metadata_address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let metadata = PositionMetadata::unpack(&metadata_account.try_borrow_data()?)?;
+        if metadata.position != *position_key
+            || pool_key.is_some_and(|pool_key| metadata.pool != *pool_key)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Empties `account`, an account of this program, moving its lamports to
+    /// `destination` and handing it back to the system program.
+    fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+        let lamports = destination
+            .lamports()
+            .checked_add(account.lamports())
+            .ok_or(SwapV3Error::Overflow)?;
+        **destination.try_borrow_mut_lamports()? = lamports;
+        **account.try_borrow_mut_lamports()? = 0;
+        account.realloc(0, false)?;
+        account.assign(&system_program::id());
+        Ok(())
+    }
+
+    /// Grows `account` to `new_len` bytes if it is smaller, with `payer` topping
+    /// up its rent first so that it stays rent exempt.
+    fn grow_account<'a>(
+        account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        new_len: usize,
+    ) -> ProgramResult {
+        if new_len <= account.data_len() {
+            return Ok(());
+        }
+        let required_lamports = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(account.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, required_lamports),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        account.realloc(new_len, true)
+    }
+
+    /// Reads the tick `tick_index` of the pool at `pool_key` from `tick_account`,
+    /// which must be the tick's PDA, so that ticks of other pools or at other
+    /// indices are rejected. See `Tick::find_address`.
+    fn load_tick(
+        tick_account: &AccountInfo,
+        pool_key: &Pubkey,
+        tick_index: i32,
+        program_id: &Pubkey,
+    ) -> Result<Tick, ProgramError> {
+        if tick_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let tick = Tick::unpack(&tick_account.try_borrow_data()?)?;
+        if tick.tick != tick_index {
+            return Err(SwapV3Error::InvalidTick.into());
+        }
+        if *tick_account.key != Tick::create_address(pool_key, tick_index, tick.bump, program_id)? {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(tick)
+    }
+
+    /// Reads the tick `tick_index` of the pool at `pool_key` like `load_tick`,
+    /// first creating its account, paid for by `payer`, if it does not exist
+    /// yet. A new tick is uninitialized and records `payer` as its rent payer,
+    /// the only account `CloseTick` returns the rent to.
+    fn load_or_create_tick<'a>(
+        tick_account: &AccountInfo<'a>,
+        pool_key: &Pubkey,
+        tick_index: i32,
+        payer: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> Result<Tick, ProgramError> {
+        if tick_account.owner == program_id {
+            return Self::load_tick(tick_account, pool_key, tick_index, program_id);
+        }
+        let (address, bump) = Tick::find_address(pool_key, tick_index, program_id);
+        if *tick_account.key != address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Self::create_pda_account(
+            tick_account,
+            payer,
+            system_program,
+            Tick::LEN,
+            &[
+                Tick::SEED,
+                pool_key.as_ref(),
+                &tick_index.to_le_bytes(),
+                &[bump],
+            ],
+            program_id,
+        )?;
+        Ok(Tick {
+            tick: tick_index,
+            rent_payer: *payer.key,
+            version: Tick::VERSION,
+            bump,
+            discriminator: Tick::DISCRIMINATOR,
+            ..Tick::default()
+        })
+    }
+
+    /// Creates `account`, the PDA of `seeds`, with `space` bytes owned by this
+    /// program and `payer` paying its rent. Anyone can send lamports to a PDA
+    /// before it exists, which would make `create_account` fail, so an account
+    /// that already holds some is topped up, allocated and assigned instead.
+    fn create_pda_account<'a>(
+        account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        space: usize,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let rent = Rent::get()?.minimum_balance(space);
+        if account.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(
+                    payer.key,
+                    account.key,
+                    rent,
+                    space as u64,
+                    program_id,
+                ),
+                &[payer.clone(), account.clone(), system_program.clone()],
+                &[seeds],
+            );
+        }
+        let top_up = rent.saturating_sub(account.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, top_up),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space as u64),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, program_id),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
+
+    /// Sets up the vaults, price oracle and tick bitmap of `pool`, at `pool_key`,
+    /// whose mints and tick spacing are already set.
+    #[allow(clippy::too_many_arguments)]
+    fn initialize_pool_accounts(
+        pool: &mut Pool,
+        pool_key: &Pubkey,
+        observations_account: &AccountInfo,
+        token_0_mint: &AccountInfo,
+        token_1_mint: &AccountInfo,
+        token_0_vault: &AccountInfo,
+        token_1_vault: &AccountInfo,
+        tick_bitmap_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if observations_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // The vaults must already be token accounts for the pool's mints, owned by its PDA.
+        let (vault_authority, vault_authority_bump) =
+            Pool::find_vault_authority(pool_key, program_id);
+        Self::check_vault(token_0_vault, token_0_mint, &vault_authority)?;
+        Self::check_vault(token_1_vault, token_1_mint, &vault_authority)?;
+        pool.token_0_vault = *token_0_vault.key;
+        pool.token_1_vault = *token_1_vault.key;
+        pool.vault_authority_bump = vault_authority_bump;
+
+        // Size the oracle's ring buffer to however many observations the account can hold.
+        let capacity = Observations::capacity(observations_account.data_len());
+        let mut observations = Observations {
+            observations: vec![Observation::default(); capacity.min(u16::MAX as usize)],
+            version: Observations::VERSION,
+        };
+        pool.observations = *observations_account.key;
+        pool.observation_index = 0;
+        let timestamp = Clock::get()?.unix_timestamp;
+        pool.observation_cardinality = oracle::initialize(&mut observations, timestamp)?;
+        pool.reward_last_updated_timestamp = timestamp;
+        pool.observation_cardinality_next = pool.observation_cardinality;
+        observations.serialize(&mut *observations_account.try_borrow_mut_data()?)?;
+
+        Self::initialize_tick_bitmap(tick_bitmap_account, pool_key, pool, program_id)
+    }
+
+    /// Sets up `tick_bitmap_account` as the tick bitmap of `pool`, at `pool_key`.
+    /// It must be a new account of this program sized for the pool's tick
+    /// spacing, so that it starts with no tick initialized and belongs to this
+    /// pool alone.
+    fn initialize_tick_bitmap(
+        tick_bitmap_account: &AccountInfo,
+        pool_key: &Pubkey,
+        pool: &mut Pool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if tick_bitmap_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if tick_bitmap_account.key == pool_key || *tick_bitmap_account.key == pool.observations {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let mut data = tick_bitmap_account.try_borrow_mut_data()?;
+        let space = TickBitmap::space(pool.tick_spacing);
+        if data.len() != space || data.iter().any(|&byte| byte != 0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        data[space - 1] = TickBitmap::VERSION;
+        pool.tick_bitmap = *tick_bitmap_account.key;
+        Ok(())
+    }
+
+    /// Ensures that `authority` is the upgrade authority of this program, as
+    /// recorded in `program_data`, the program's data account, and signed the
+    /// transaction.
+    fn check_upgrade_authority(
+        program_data: &AccountInfo,
+        authority: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (program_data_address, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        if *program_data.owner != bpf_loader_upgradeable::id()
+            || *program_data.key != program_data_address
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // The account starts with its `UpgradeableLoaderState`: the u32 tag of
+        // `ProgramData` (3), the u64 slot of the last deployment and the
+        // optional upgrade authority, as a bool followed by the pubkey.
+        let data = program_data.try_borrow_data()?;
+        if data.len() < 45 || data[..4] != [3, 0, 0, 0] || data[12] != 1 {
+            return Err(SwapV3Error::Unauthorized.into());
+        }
+        let upgrade_authority =
+            Pubkey::try_from(&data[13..45]).map_err(|_| ProgramError::InvalidAccountData)?;
+        if *authority.key != upgrade_authority {
+            return Err(SwapV3Error::Unauthorized.into());
+        }
+        Ok(())
+    }
+
+    /// Ensures that `admin` is the pool's admin and signed the transaction.
+    fn check_admin(pool: &Pool, admin: &AccountInfo) -> ProgramResult {
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *admin.key != pool.admin {
+            return Err(SwapV3Error::Unauthorized.into());
+        }
+        Ok(())
+    }
+
+    /// Ensures that `mint` is a supported Token or Token-2022 mint and `vault` a
+    /// token account for it, of the same token program, owned by `authority`.
+    fn check_vault(vault: &AccountInfo, mint: &AccountInfo, authority: &Pubkey) -> ProgramResult {
+        token::unpack_mint(mint)?;
+        if vault.owner != mint.owner {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_account = token::unpack_account(vault)?;
+        if token_account.mint != *mint.key || token_account.owner != *authority {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Returns the balance of a token account.
+    fn token_balance(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+        Ok(token::unpack_account(token_account)?.amount)
+    }
+
+    /// Transfers `amount` tokens out of one of the pool's vaults, signing as the vault authority.
+    fn transfer_from_vault<'a>(
+        pool: &Pool,
+        pool_key: &Pubkey,
+        vault: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        vault_authority: &AccountInfo<'a>,
+        token: &TokenMint<'a, '_>,
+        amount: u64,
+    ) -> ProgramResult {
+        token.transfer(
+            vault,
+            destination,
+            vault_authority,
+            amount,
+            &[&[
+                Pool::VAULT_AUTHORITY_SEED,
+                pool_key.as_ref(),
+                &[pool.vault_authority_bump],
+            ]],
+        )
+    }
+
+    /// Applies a change of `liquidity_delta` to `position` and the ticks
+    /// bounding it, as `_modifyPosition` does in Uniswap v3: the ticks are
+    /// updated, the position is credited with what it earned at its old
+    /// liquidity, and, when the range contains the current tick, an oracle
+    /// observation is written before the pool's in-range liquidity changes, so
+    /// that the elapsed period is accumulated with the liquidity it had. Ticks
+    /// that flip between initialized and uninitialized are flipped in the
+    /// pool's tick bitmap too.
+    fn modify_position(
+        pool: &mut Pool,
+        observations: &mut Observations,
+        bitmap: &mut [u8],
+        position: &mut Position,
+        tick_lower: &mut Tick,
+        tick_upper: &mut Tick,
+        liquidity_delta: i128,
+    ) -> ProgramResult {
+        let timestamp = Clock::get()?.unix_timestamp;
+        reward::update_growths(pool, timestamp)?;
+        let globals = Self::global_accumulators(pool, observations, timestamp)?;
+
+        for (tick, tick_index, upper) in [
+            (&mut *tick_lower, position.tick_lower, false),
+            (&mut *tick_upper, position.tick_upper, true),
+        ] {
+            let flipped = tick::update(
+                tick,
+                tick_index,
+                pool.current_tick,
+                liquidity_delta,
+                upper,
+                &globals,
+            )?;
+            if flipped {
+                tick_bitmap::set(
+                    bitmap,
+                    tick_index,
+                    pool.tick_spacing,
+                    tick.liquidity_gross != 0,
+                )?;
+            }
+        }
+
+        // Credit the fees and rewards earned so far before the position's liquidity changes.
+        tick::update_position_fees(
+            position,
+            tick::fee_growths_inside(tick_lower, tick_upper, pool),
+        )?;
+        let reward_growths_inside = reward::growths_inside(
+            tick_lower,
+            tick_upper,
+            pool.current_tick,
+            &globals.reward_growths_global_x64,
+        );
+        reward::update_position(position, &reward_growths_inside)?;
+        position.liquidity = add_liquidity_delta(position.liquidity, liquidity_delta)?;
+
+        if pool.current_tick >= position.tick_lower && pool.current_tick < position.tick_upper {
+            (pool.observation_index, pool.observation_cardinality) = oracle::write(
+                observations,
+                pool.observation_index,
+                pool.observation_cardinality,
+                pool.observation_cardinality_next,
+                timestamp,
+                pool.current_tick,
+                pool.activity_liquidity,
+            )?;
+            pool.activity_liquidity =
+                add_liquidity_delta(pool.activity_liquidity, liquidity_delta)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the token amounts a change of `liquidity_delta` in the range
+    /// `[tick_lower, tick_upper)` moves at the pool's current price: only
+    /// token_0 below the range, only token_1 above it, and both inside it.
+    /// Deposits (positive) are rounded up and withdrawals (negative) down.
+    fn amounts_for_liquidity_delta(
+        pool: &Pool,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(i128, i128), ProgramError> {
+        let sqrt_lower = get_sqrt_ratio_at_tick(tick_lower)?;
+        let sqrt_upper = get_sqrt_ratio_at_tick(tick_upper)?;
+        if pool.current_tick < tick_lower {
+            Ok((
+                get_amount0_delta_signed(sqrt_lower, sqrt_upper, liquidity_delta)?,
+                0,
+            ))
+        } else if pool.current_tick < tick_upper {
+            Ok((
+                get_amount0_delta_signed(pool.sqrt_price, sqrt_upper, liquidity_delta)?,
+                get_amount1_delta_signed(sqrt_lower, pool.sqrt_price, liquidity_delta)?,
+            ))
+        } else {
+            Ok((
+                0,
+                get_amount1_delta_signed(sqrt_lower, sqrt_upper, liquidity_delta)?,
+            ))
+        }
+    }
+
+    /// Collects the pool-wide accumulators as of `timestamp`, reading the
+    /// oracle cumulatives from the newest observation.
+    fn global_accumulators(
+        pool: &Pool,
+        observations: &Observations,
+        timestamp: i64,
+    ) -> Result<GlobalAccumulators, ProgramError> {
+        let (tick_cumulative, seconds_per_liquidity_cumulative_x64) = oracle::observe_single(
+            observations,
+            timestamp,
+            0,
+            pool.current_tick,
+            pool.observation_index,
+            pool.activity_liquidity,
+            pool.observation_cardinality,
+        )?;
+
+        Ok(GlobalAccumulators {
+            fee_growth_global_0_x64: pool.fee_growth_global_0_x64,
+            fee_growth_global_1_x64: pool.fee_growth_global_1_x64,
+            tick_cumulative,
+            seconds_per_liquidity_cumulative_x64,
+            timestamp,
+            reward_growths_global_x64: reward::growths_global(pool),
+        })
+    }
 }
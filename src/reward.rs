@@ -0,0 +1,93 @@
+use crate::{
+    error::SwapV3Error,
+    state::{Pool, Position, Tick},
+    utils::{Q64, mul_div_floor},
+};
+use solana_program::program_error::ProgramError;
+
+/// Accrues the emissions of every reward slot since the last update to the
+/// pool's in-range liquidity.
+///
+/// Must be called before the in-range liquidity changes, a tick is crossed, or
+/// the reward growth inside a range is read.
+///
+/// # Arguments
+/// * `pool` - The pool to update.
+/// * `timestamp` - The current unix timestamp.
+pub fn update_growths(pool: &mut Pool, timestamp: i64) -> Result<(), ProgramError> {
+    let elapsed = timestamp.saturating_sub(pool.reward_last_updated_timestamp);
+    if elapsed > 0 && pool.activity_liquidity > 0 {
+        for reward in pool.reward_infos.iter_mut() {
+            if !reward.initialized() || reward.emissions_per_second_x64 == 0 {
+                continue;
+            }
+            // Growths are allowed to wrap; only differences between them are meaningful.
+            let growth_delta = mul_div_floor(
+                reward.emissions_per_second_x64,
+                elapsed as u128,
+                pool.activity_liquidity,
+            )?;
+            reward.growth_global_x64 = reward.growth_global_x64.wrapping_add(growth_delta);
+        }
+    }
+    pool.reward_last_updated_timestamp = timestamp;
+    Ok(())
+}
+
+/// Returns the growth of each reward inside the range `[lower, upper)`.
+///
+/// # Arguments
+/// * `lower` - The lower tick of the range.
+/// * `upper` - The upper tick of the range.
+/// * `current_tick` - The pool's current tick.
+/// * `growths_global_x64` - The pool's global growth of each reward.
+pub fn growths_inside(
+    lower: &Tick,
+    upper: &Tick,
+    current_tick: i32,
+    growths_global_x64: &[u128; Pool::NUM_REWARDS],
+) -> [u128; Pool::NUM_REWARDS] {
+    let mut growths_inside = [0; Pool::NUM_REWARDS];
+    for (i, growth_inside) in growths_inside.iter_mut().enumerate() {
+        let global = growths_global_x64[i];
+        let below = if current_tick >= lower.tick {
+            lower.reward_growths_outside_x64[i]
+        } else {
+            global.wrapping_sub(lower.reward_growths_outside_x64[i])
+        };
+        let above = if current_tick < upper.tick {
+            upper.reward_growths_outside_x64[i]
+        } else {
+            global.wrapping_sub(upper.reward_growths_outside_x64[i])
+        };
+        *growth_inside = global.wrapping_sub(below).wrapping_sub(above);
+    }
+    growths_inside
+}
+
+/// Credits a position with the rewards its liquidity earned since its last
+/// update. Must be called before the position's liquidity changes.
+///
+/// # Arguments
+/// * `position` - The position to update.
+/// * `growths_inside_x64` - The current reward growth inside the position's range.
+pub fn update_position(
+    position: &mut Position,
+    growths_inside_x64: &[u128; Pool::NUM_REWARDS],
+) -> Result<(), ProgramError> {
+    for (reward, &growth_inside) in position.reward_infos.iter_mut().zip(growths_inside_x64) {
+        let growth_delta = growth_inside.wrapping_sub(reward.growth_inside_last_x64);
+        let earned = mul_div_floor(growth_delta, position.liquidity, Q64)?;
+        reward.amount_owed = u64::try_from(earned)
+            .ok()
+            .and_then(|earned| reward.amount_owed.checked_add(earned))
+            .ok_or(SwapV3Error::Overflow)?;
+        reward.growth_inside_last_x64 = growth_inside;
+    }
+    Ok(())
+}
+
+/// Returns the global growth of each of the pool's rewards.
+pub fn growths_global(pool: &Pool) -> [u128; Pool::NUM_REWARDS] {
+    pool.reward_infos.map(|reward| reward.growth_global_x64)
+}
@@ -1,5 +1,45 @@
+use crate::{
+    error::SwapV3Error,
+    utils::{MAX_TICK, MIN_TICK, SqrtPriceX64, get_tick_at_sqrt_ratio},
+};
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Checks the version byte of an account, found at `offset`, right after the
+/// fields of the layout that introduced versions. Accounts written before then
+/// end before that byte or hold zero padding there, and must be migrated with
+/// `Migrate` first.
+fn check_version(data: &[u8], offset: usize, version: u8) -> Result<(), ProgramError> {
+    match data.get(offset).copied() {
+        Some(found) if found == version => Ok(()),
+        Some(found) if found > version => Err(SwapV3Error::UnsupportedAccountVersion.into()),
+        _ => Err(SwapV3Error::AccountVersionOutdated.into()),
+    }
+}
+
+/// Checks the discriminator byte of an account, found at `offset`, which tells
+/// the kinds of accounts of similar size apart. Accounts of the current
+/// version always have it set, so a mismatch means another kind of account.
+/// Observations and tick bitmap accounts are only ever used through the key
+/// their pool records, so they need none.
+fn check_discriminator(data: &[u8], offset: usize, discriminator: u8) -> Result<(), ProgramError> {
+    if data.get(offset) != Some(&discriminator) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// The kinds of accounts `Migrate` can upgrade from the baseline layout. A
+/// pool's observations and tick bitmap accounts are set up with the pool.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq)]
+pub enum AccountType {
+    /// A `Pool` account.
+    Pool,
+    /// A `Tick` account.
+    Tick,
+    /// A `Position` account.
+    Position,
+}
 
 /// Represents a liquidity pool in the SwapV3 program.
 /// This struct holds all the global state for a single pool.
@@ -9,27 +49,218 @@ pub struct Pool {
     pub token_0_mint: Pubkey,
     /// The mint address of the second token (token_1).
     pub token_1_mint: Pubkey,
-    /// The fee tier for this pool, in basis points (e.g., 500 for 0.50%).
+    /// The fee tier for this pool, in hundredths of a basis point (e.g., 500 for 0.05%).
     pub fee: u32,
     /// The spacing between usable ticks.
     pub tick_spacing: u16,
-    /// The total protocol fees collected in token_0.
+    /// The total LP fees paid in token_0 by swaps and flash loans. The pool
+    /// takes no protocol fee: these are shared among in-range liquidity through
+    /// `fee_growth_global_0_x64`.
     pub total_fee0: u128,
-    /// The total protocol fees collected in token_1.
+    /// The total LP fees paid in token_1 by flash loans, shared among in-range
+    /// liquidity through `fee_growth_global_1_x64`.
     pub total_fee1: u128,
     /// The total active liquidity in the pool.
     pub activity_liquidity: u128,
-    /// The current price of the pool, represented as a Q64.64 fixed-point number.
-    pub sqrt_price_x64: u128,
+    /// The current sqrt price of the pool.
+    pub sqrt_price: SqrtPriceX64,
     /// The current tick index of the pool.
     pub current_tick: i32,
+    /// The account holding this pool's price oracle observations.
+    pub observations: Pubkey,
+    /// The index of the most recently written observation.
+    pub observation_index: u16,
+    /// The number of observations currently in use in the ring buffer.
+    pub observation_cardinality: u16,
+    /// The number of observations the ring buffer will grow to use once the
+    /// current cardinality has been filled.
+    pub observation_cardinality_next: u16,
+    /// The token_0 fees earned per unit of in-range liquidity over the life of the pool, as a Q64.64 number.
+    pub fee_growth_global_0_x64: u128,
+    /// The token_1 fees earned per unit of in-range liquidity over the life of the pool, as a Q64.64 number.
+    pub fee_growth_global_1_x64: u128,
+    /// The token account holding the pool's token_0 reserves.
+    pub token_0_vault: Pubkey,
+    /// The token account holding the pool's token_1 reserves.
+    pub token_1_vault: Pubkey,
+    /// The bump seed of the PDA that owns the vaults. See `Pool::find_vault_authority`.
+    pub vault_authority_bump: u8,
+    /// The amount of token_0 owed by a swap that deferred its payment to a
+    /// `SettleSwap` instruction later in the same transaction.
+    pub swap_debt: u64,
+    /// The account allowed to configure the pool, such as its reward emissions.
+    pub admin: Pubkey,
+    /// The unix timestamp up to which the reward growths have been accrued.
+    pub reward_last_updated_timestamp: i64,
+    /// The liquidity-mining reward slots. A slot is in use once its mint is set.
+    pub reward_infos: [RewardInfo; Pool::NUM_REWARDS],
+    /// The volatility-based fee charged on top of `fee`, disabled until the admin configures it.
+    pub dynamic_fee: DynamicFee,
+    /// The layout version of the account. See `Pool::VERSION`.
+    pub version: u8,
+    /// The account holding the bitmap of this pool's initialized ticks. See `TickBitmap`.
+    pub tick_bitmap: Pubkey,
+    /// Identifies the account as a pool. See `Pool::DISCRIMINATOR`.
+    pub discriminator: u8,
 }
 
 impl Pool {
     // Define the size of the Pool struct for account allocation.
     // Pubkey (32) * 2 + u32 (4) + u16 (2) + u128 (16) * 4 + i32 (4) = 64 + 4 + 2 + 64 + 4 = 138
+    // Oracle: Pubkey (32) + u16 (2) * 3 = 38, taken from the padding.
+    // Fee growth: u128 (16) * 2 = 32, taken from the padding.
+    // Vaults: Pubkey (32) * 2 + u8 (1) = 65, one byte taken from the padding.
+    // Swap debt: u64 (8), taken from the padding.
+    // Rewards: Pubkey (32) + i64 (8) + RewardInfo (96) * 3 = 328
+    // Dynamic fee: DynamicFee (34), taken from the padding.
+    // Version: u8 (1), taken from the padding.
+    // Tick bitmap: Pubkey (32), after the version byte.
+    // Discriminator: u8 (1), taken from the padding.
     // Add padding for future expansion.
-    pub const LEN: usize = 32 + 32 + 4 + 2 + 16 + 16 + 16 + 16 + 4 + 128;
+    pub const LEN: usize = 32
+        + 32
+        + 4
+        + 2
+        + 16
+        + 16
+        + 16
+        + 16
+        + 4
+        + 32
+        + 2
+        + 2
+        + 2
+        + 16
+        + 16
+        + 32
+        + 32
+        + 1
+        + 8
+        + 32
+        + 8
+        + RewardInfo::LEN * Pool::NUM_REWARDS
+        + DynamicFee::LEN
+        + 1
+        + 32
+        + 1
+        + 13;
+
+    /// The layout version of pool accounts written by this program.
+    pub const VERSION: u8 = 3;
+
+    /// The offset of the version byte, right before the tick bitmap, the
+    /// discriminator and the padding.
+    pub const VERSION_OFFSET: usize = Self::LEN - 13 - 1 - 32 - 1;
+
+    /// The value of the discriminator byte of pool accounts.
+    pub const DISCRIMINATOR: u8 = 1;
+
+    /// The offset of the discriminator byte, right before the padding.
+    pub const DISCRIMINATOR_OFFSET: usize = Self::LEN - 13 - 1;
+
+    /// The number of reward slots of a pool.
+    pub const NUM_REWARDS: usize = 3;
+
+    /// The size of pool accounts of the baseline layout, written before layouts
+    /// were versioned.
+    // The fields up to `current_tick` (138), then 128 bytes of padding.
+    pub const BASELINE_LEN: usize = 32 + 32 + 4 + 2 + 16 + 16 + 16 + 16 + 4 + 128;
+
+    /// The seed prefix of the vault authority PDA.
+    pub const VAULT_AUTHORITY_SEED: &'static [u8] = b"vault_authority";
+
+    /// Reads a pool account, which must have the current layout.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        check_version(data, Self::VERSION_OFFSET, Self::VERSION)?;
+        check_discriminator(data, Self::DISCRIMINATOR_OFFSET, Self::DISCRIMINATOR)?;
+        // The account is allocated with trailing padding, so only read the prefix.
+        Ok(Self::deserialize(&mut &data[..])?)
+    }
+
+    /// Reads a pool account of the baseline layout into the current one. The
+    /// baseline took no deposits, so the liquidity it recorded is not carried
+    /// over, and it did not track the current tick, which is derived from the
+    /// price again. The fields it did not have read as zero.
+    pub fn from_baseline(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::BASELINE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // The baseline fields are a prefix of the current layout.
+        let mut current = vec![0; Self::LEN];
+        current[..Self::BASELINE_LEN - 128].copy_from_slice(&data[..Self::BASELINE_LEN - 128]);
+        let mut pool = Self::deserialize(&mut &current[..])?;
+        pool.activity_liquidity = 0;
+        pool.current_tick = get_tick_at_sqrt_ratio(pool.sqrt_price)?;
+        pool.version = Self::VERSION;
+        pool.discriminator = Self::DISCRIMINATOR;
+        Ok(pool)
+    }
+
+    /// Finds the PDA that owns the vaults of the pool at `pool`, and its bump seed.
+    pub fn find_vault_authority(pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::VAULT_AUTHORITY_SEED, pool.as_ref()], program_id)
+    }
+}
+
+/// A liquidity-mining reward emitted to the pool's in-range liquidity.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub struct RewardInfo {
+    /// The mint of the reward token.
+    pub mint: Pubkey,
+    /// The token account holding the reward tokens, owned by the pool's vault authority.
+    pub vault: Pubkey,
+    /// The amount of reward tokens emitted per second, as a Q64.64 number.
+    pub emissions_per_second_x64: u128,
+    /// The rewards earned per unit of in-range liquidity over the life of the reward, as a Q64.64 number.
+    pub growth_global_x64: u128,
+}
+
+impl RewardInfo {
+    // Pubkey (32) * 2 + u128 (16) * 2 = 96
+    pub const LEN: usize = 32 + 32 + 16 + 16;
+
+    /// Whether the reward slot is in use.
+    pub fn initialized(&self) -> bool {
+        self.mint != Pubkey::default()
+    }
+}
+
+/// The configuration and state of a pool's volatility-based fee.
+///
+/// Each swap step measures how far the price has moved from a reference tick,
+/// in tick spacings, on top of a reference volatility carried over from earlier
+/// swaps. The variable fee grows with the square of that volatility and the
+/// carried-over volatility decays while the pool is quiet.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub struct DynamicFee {
+    /// Swaps less than this many seconds after the previous one keep its references.
+    pub filter_period: u16,
+    /// After this many seconds without a swap, the carried-over volatility is dropped.
+    pub decay_period: u16,
+    /// The share of the volatility carried over to the next period, in basis points.
+    pub reduction_factor: u16,
+    /// Scales the square of the volatility into the variable fee. Zero disables the variable fee.
+    pub variable_fee_control: u32,
+    /// The largest value of `volatility_accumulator`.
+    pub max_volatility_accumulator: u32,
+    /// The volatility of the latest swap step, in ten-thousandths of a tick spacing.
+    pub volatility_accumulator: u32,
+    /// The volatility carried over from earlier swaps, in ten-thousandths of a tick spacing.
+    pub volatility_reference: u32,
+    /// The tick the price movement of the current period is measured from.
+    pub tick_reference: i32,
+    /// The unix timestamp of the latest swap.
+    pub last_update_timestamp: i64,
+}
+
+impl DynamicFee {
+    // u16 (2) * 3 + u32 (4) * 4 + i32 (4) + i64 (8) = 34
+    pub const LEN: usize = 2 + 2 + 2 + 4 + 4 + 4 + 4 + 4 + 8;
+
+    /// Whether the pool charges a variable fee.
+    pub fn enabled(&self) -> bool {
+        self.variable_fee_control > 0
+    }
 }
 
 /// Represents a single tick in the price range.
@@ -40,21 +271,149 @@ pub struct Tick {
     pub tick: i32,
     /// The net change in liquidity when this tick is crossed.
     pub liquidity: i128,
+    /// The total liquidity of the positions using this tick as a boundary.
+    /// A tick is initialized while this is non-zero.
+    pub liquidity_gross: u128,
     /// The total fee growth for token_0 outside of this tick.
     pub fee_growth_outside_0: u128,
     /// The total fee growth for token_1 outside of this tick.
     pub fee_growth_outside_1: u128,
+    /// The tick cumulative accumulated on the other side of this tick.
+    pub tick_cumulative_outside: i64,
+    /// The seconds per liquidity accumulated on the other side of this tick, as a Q64.64 number.
+    pub seconds_per_liquidity_outside_x64: u128,
+    /// The seconds spent on the other side of this tick.
+    pub seconds_outside: u32,
+    /// The liquidity of the unfilled range orders that have this tick as their lower boundary.
+    pub range_order_liquidity: u128,
+    /// The number of times the range orders at this tick have been filled.
+    pub range_order_epoch: u32,
+    /// The growth of each of the pool's rewards outside of this tick.
+    pub reward_growths_outside_x64: [u128; Pool::NUM_REWARDS],
+    /// The account that paid for this tick account's rent, to which `CloseTick`
+    /// returns it once the tick is no longer used.
+    pub rent_payer: Pubkey,
+    /// The layout version of the account. See `Tick::VERSION`.
+    pub version: u8,
+    /// The bump seed of the tick's address. See `Tick::find_address`.
+    pub bump: u8,
+    /// Identifies the account as a tick. See `Tick::DISCRIMINATOR`.
+    pub discriminator: u8,
 }
 
 impl Tick {
+    // i32 (4) + i128 (16) + u128 (16) * 3 + i64 (8) + u128 (16) + u32 (4) + u128 (16) + u32 (4) = 116
+    // Rewards: u128 (16) * 3 = 48
+    // Rent payer: Pubkey (32)
+    // Version: u8 (1)
+    // Bump: u8 (1), taken from the padding.
+    // Discriminator: u8 (1), taken from the padding.
+    // Add padding for future expansion.
+    pub const LEN: usize =
+        4 + 16 + 16 + 16 + 16 + 8 + 16 + 4 + 16 + 4 + 16 * Pool::NUM_REWARDS + 32 + 1 + 1 + 1 + 61;
+
+    /// The layout version of tick accounts written by this program.
+    pub const VERSION: u8 = 3;
+
+    /// The offset of the version byte, right before the bump seed, the
+    /// discriminator and the padding.
+    pub const VERSION_OFFSET: usize = Self::LEN - 61 - 1 - 1 - 1;
+
+    /// The value of the discriminator byte of tick accounts.
+    pub const DISCRIMINATOR: u8 = 2;
+
+    /// The offset of the discriminator byte, right before the padding.
+    pub const DISCRIMINATOR_OFFSET: usize = Self::LEN - 61 - 1;
+
+    /// The seed prefix of tick PDAs.
+    pub const SEED: &'static [u8] = b"tick";
+
+    /// The size of tick accounts of the baseline layout, written before layouts
+    /// were versioned.
     // i32 (4) + i128 (16) + u128 (16) * 2 = 4 + 16 + 32 = 52
-    pub const LEN: usize = 4 + 16 + 16 + 16;
+    pub const BASELINE_LEN: usize = 4 + 16 + 16 + 16;
+
+    /// Reads a tick account, which must have the current layout.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        check_version(data, Self::VERSION_OFFSET, Self::VERSION)?;
+        check_discriminator(data, Self::DISCRIMINATOR_OFFSET, Self::DISCRIMINATOR)?;
+        Ok(Self::deserialize(&mut &data[..])?)
+    }
+
+    /// Finds the account of the tick `tick` of the pool at `pool`, and its bump seed.
+    pub fn find_address(pool: &Pubkey, tick: i32, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[Self::SEED, pool.as_ref(), &tick.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    /// Returns the account of the tick `tick` of the pool at `pool`, given its bump seed.
+    pub fn create_address(
+        pool: &Pubkey,
+        tick: i32,
+        bump: u8,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(
+            &[Self::SEED, pool.as_ref(), &tick.to_le_bytes(), &[bump]],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)
+    }
+}
+
+/// The bitmap of a pool's initialized ticks, with one bit for each multiple of
+/// the tick spacing from `MIN_TICK` to `MAX_TICK`, stored as little-endian u64
+/// words followed by the layout version. Swaps search it for the next tick to
+/// cross, so no initialized tick can be skipped. See the `tick_bitmap` module.
+pub struct TickBitmap;
+
+impl TickBitmap {
+    /// The layout version of tick bitmap accounts written by this program.
+    pub const VERSION: u8 = 1;
+
+    /// Returns the number of words tracking the ticks of a pool with `tick_spacing`.
+    pub const fn word_count(tick_spacing: u16) -> usize {
+        let spacing = if tick_spacing == 0 {
+            1
+        } else {
+            tick_spacing as i32
+        };
+        let bits = (MAX_TICK.div_euclid(spacing) - MIN_TICK.div_euclid(spacing) + 1) as usize;
+        bits.div_ceil(64)
+    }
+
+    /// Returns the account size of the tick bitmap of a pool with `tick_spacing`.
+    // u64 (8) * word count + version (1)
+    pub const fn space(tick_spacing: u16) -> usize {
+        8 * Self::word_count(tick_spacing) + 1
+    }
+
+    /// Returns the words of a tick bitmap account, which must have the current
+    /// layout and the size for `tick_spacing`.
+    pub fn words(data: &[u8], tick_spacing: u16) -> Result<&[u8], ProgramError> {
+        let space = Self::space(tick_spacing);
+        if data.len() != space {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        check_version(data, space - 1, Self::VERSION)?;
+        Ok(&data[..space - 1])
+    }
+
+    /// Returns the words of a tick bitmap account for writing. See `TickBitmap::words`.
+    pub fn words_mut(data: &mut [u8], tick_spacing: u16) -> Result<&mut [u8], ProgramError> {
+        Self::words(data, tick_spacing)?;
+        let space = data.len();
+        Ok(&mut data[..space - 1])
+    }
 }
 
 /// Represents a user's liquidity position in a specific range.
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct Position {
-    /// The owner of this position.
+    /// The owner of this position. For positions opened with an NFT, this is the
+    /// account that opened it; the position is then controlled by whoever holds the NFT.
     pub owner: Pubkey,
     /// The lower tick boundary of the position.
     pub tick_lower: i32,
@@ -62,13 +421,238 @@ pub struct Position {
     pub tick_upper: i32,
     /// The amount of liquidity provided by this position.
     pub liquidity: u128,
-    /// The fees in token_0 collected since the last withdrawal.
-    pub collected_fee0: u128,
-    /// The fees in token_1 collected since the last withdrawal.
-    pub collected_fee1: u128,
+    /// The token_0 fee growth inside the position's range as of its last
+    /// update, as a Q64.64 number.
+    pub fee_growth_inside_0_last_x64: u128,
+    /// The token_1 fee growth inside the position's range as of its last
+    /// update, as a Q64.64 number.
+    pub fee_growth_inside_1_last_x64: u128,
+    /// Whether this position is a range order, which is closed once the price crosses it.
+    pub range_order: bool,
+    /// The `range_order_epoch` of the lower tick when the range order was placed.
+    /// The order has been filled once the tick's epoch has moved past it.
+    pub range_order_epoch: u32,
+    /// The position's share of each of the pool's rewards.
+    pub reward_infos: [PositionRewardInfo; Pool::NUM_REWARDS],
+    /// The mint of the NFT whose holder controls this position, or the default
+    /// pubkey for positions controlled by `owner`.
+    pub position_mint: Pubkey,
+    /// The layout version of the account. See `Position::VERSION`.
+    pub version: u8,
+    /// The token_0 fees earned by the position and not yet collected.
+    pub tokens_owed_0: u64,
+    /// The token_1 fees earned by the position and not yet collected.
+    pub tokens_owed_1: u64,
+    /// The pool the position belongs to. Positions migrated from the baseline
+    /// layout, which did not record it, have the default pubkey, which matches
+    /// no pool.
+    pub pool: Pubkey,
+    /// Identifies the account as a position. See `Position::DISCRIMINATOR`.
+    pub discriminator: u8,
 }
 
 impl Position {
+    // Pubkey (32) + i32 (4) * 2 + u128 (16) * 3 + bool (1) + u32 (4) = 32 + 8 + 48 + 5 = 93
+    // Rewards: PositionRewardInfo (24) * 3 = 72
+    // Position mint: Pubkey (32)
+    // Version: u8 (1)
+    // Tokens owed: u64 (8) * 2, after the version byte.
+    // Pool: Pubkey (32)
+    // Discriminator: u8 (1), taken from the padding.
+    // Add padding for future expansion.
+    pub const LEN: usize = 32
+        + 4
+        + 4
+        + 16
+        + 16
+        + 16
+        + 1
+        + 4
+        + PositionRewardInfo::LEN * Pool::NUM_REWARDS
+        + 32
+        + 1
+        + 8
+        + 8
+        + 32
+        + 1
+        + 14;
+
+    /// The layout version of position accounts written by this program.
+    pub const VERSION: u8 = 2;
+
+    /// The offset of the version byte, right before the tokens owed, the pool,
+    /// the discriminator and the padding.
+    pub const VERSION_OFFSET: usize = Self::LEN - 14 - 1 - 32 - 16 - 1;
+
+    /// The value of the discriminator byte of position accounts.
+    pub const DISCRIMINATOR: u8 = 3;
+
+    /// The offset of the discriminator byte, right before the padding.
+    pub const DISCRIMINATOR_OFFSET: usize = Self::LEN - 14 - 1;
+
+    /// The size of position accounts of the baseline layout, written before
+    /// layouts were versioned.
     // Pubkey (32) + i32 (4) * 2 + u128 (16) * 3 = 32 + 8 + 48 = 88
-    pub const LEN: usize = 32 + 4 + 4 + 16 + 16 + 16;
+    pub const BASELINE_LEN: usize = 32 + 4 + 4 + 16 + 16 + 16;
+
+    /// Whether `data` is a new position account that the program has not
+    /// written yet: exactly `Position::LEN` bytes, all zero. Other accounts the
+    /// program owns are never new positions, whatever their content.
+    pub fn is_new(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data.iter().all(|&byte| byte == 0)
+    }
+
+    /// Reads a position account, which must have the current layout. A new
+    /// account that the program has not written yet reads as an empty position.
+    /// See `Position::is_new`.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if !Self::is_new(data) {
+            check_version(data, Self::VERSION_OFFSET, Self::VERSION)?;
+            check_discriminator(data, Self::DISCRIMINATOR_OFFSET, Self::DISCRIMINATOR)?;
+        }
+        let mut position = Self::deserialize(&mut &data[..])?;
+        position.version = Self::VERSION;
+        position.discriminator = Self::DISCRIMINATOR;
+        Ok(position)
+    }
+
+    /// Reads a position account of the baseline layout into the current one.
+    /// The baseline took no deposits and did not record the position's pool,
+    /// so only the owner and range are carried over: the position belongs to
+    /// no pool and holds nothing, and its owner may only close it.
+    pub fn from_baseline(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::BASELINE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // The owner and range are a prefix of both layouts.
+        let mut current = vec![0; Self::LEN];
+        current[..32 + 4 + 4].copy_from_slice(&data[..32 + 4 + 4]);
+        let mut position = Self::deserialize(&mut &current[..])?;
+        position.version = Self::VERSION;
+        position.discriminator = Self::DISCRIMINATOR;
+        Ok(position)
+    }
+}
+
+/// Describes the position an NFT controls, stored in an account derived from the NFT's mint.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq)]
+pub struct PositionMetadata {
+    /// The pool the position belongs to.
+    pub pool: Pubkey,
+    /// The position account.
+    pub position: Pubkey,
+    /// The mint of the position NFT.
+    pub position_mint: Pubkey,
+    /// The lower tick boundary of the position.
+    pub tick_lower: i32,
+    /// The upper tick boundary of the position.
+    pub tick_upper: i32,
+    /// The layout version of the account. See `PositionMetadata::VERSION`.
+    pub version: u8,
+    /// Identifies the account as position metadata. See `PositionMetadata::DISCRIMINATOR`.
+    pub discriminator: u8,
+}
+
+impl PositionMetadata {
+    // Pubkey (32) * 3 + i32 (4) * 2 = 104
+    // Version: u8 (1)
+    // Discriminator: u8 (1)
+    pub const LEN: usize = 32 + 32 + 32 + 4 + 4 + 1 + 1;
+
+    /// The layout version of position metadata accounts written by this program.
+    pub const VERSION: u8 = 2;
+
+    /// The offset of the version byte, right before the discriminator.
+    pub const VERSION_OFFSET: usize = Self::LEN - 1 - 1;
+
+    /// The value of the discriminator byte of position metadata accounts.
+    pub const DISCRIMINATOR: u8 = 4;
+
+    /// The offset of the discriminator byte, the last of the account.
+    pub const DISCRIMINATOR_OFFSET: usize = Self::LEN - 1;
+
+    /// The seed prefix of the position metadata PDA.
+    pub const SEED: &'static [u8] = b"position_metadata";
+
+    /// Reads a position metadata account, which must have the current layout.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        check_version(data, Self::VERSION_OFFSET, Self::VERSION)?;
+        check_discriminator(data, Self::DISCRIMINATOR_OFFSET, Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    /// Finds the metadata account of the position NFT `position_mint`, and its bump seed.
+    pub fn find_address(position_mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, position_mint.as_ref()], program_id)
+    }
+}
+
+/// A position's share of one of the pool's rewards.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub struct PositionRewardInfo {
+    /// The reward growth inside the position's range as of its last update, as a Q64.64 number.
+    pub growth_inside_last_x64: u128,
+    /// The rewards earned by the position and not yet collected.
+    pub amount_owed: u64,
+}
+
+impl PositionRewardInfo {
+    // u128 (16) + u64 (8) = 24
+    pub const LEN: usize = 16 + 8;
+}
+
+/// A single price oracle observation, recording cumulative values at a point in time.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq)]
+pub struct Observation {
+    /// The unix timestamp at which the observation was written.
+    pub timestamp: i64,
+    /// The tick index multiplied by the seconds elapsed, accumulated since the pool was initialized.
+    pub tick_cumulative: i64,
+    /// The seconds elapsed divided by the in-range liquidity, accumulated since the
+    /// pool was initialized, as a Q64.64 number.
+    pub seconds_per_liquidity_cumulative_x64: u128,
+    /// Whether this observation has been written.
+    pub initialized: bool,
+}
+
+impl Observation {
+    // i64 (8) * 2 + u128 (16) + bool (1) = 33
+    pub const LEN: usize = 8 + 8 + 16 + 1;
+}
+
+/// The price oracle history of a pool, stored as a ring buffer of observations.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct Observations {
+    /// The observation slots; the first `Pool.observation_cardinality` of them are in use.
+    pub observations: Vec<Observation>,
+    /// The layout version of the account, which follows the observation slots.
+    /// See `Observations::VERSION`.
+    pub version: u8,
+}
+
+impl Observations {
+    /// The layout version of observations accounts written by this program.
+    pub const VERSION: u8 = 1;
+
+    /// Returns the account size needed to hold `capacity` observations.
+    // Vec length prefix (4) + Observation::LEN * capacity + version (1)
+    pub const fn space(capacity: usize) -> usize {
+        4 + Observation::LEN * capacity + 1
+    }
+
+    /// Returns the number of observations that fit in an account of `data_len` bytes.
+    pub const fn capacity(data_len: usize) -> usize {
+        data_len.saturating_sub(4 + 1) / Observation::LEN
+    }
+
+    /// Reads an observations account, which must have the current layout.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let rest = &mut &data[..];
+        let observations = Vec::<Observation>::deserialize(rest)?;
+        check_version(rest, 0, Self::VERSION)?;
+        Ok(Self {
+            observations,
+            version: Self::VERSION,
+        })
+    }
 }
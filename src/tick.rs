@@ -0,0 +1,339 @@
+use crate::{
+    error::SwapV3Error,
+    state::{Pool, Position, Tick},
+    utils::{Q64, add_liquidity_delta, mul_div_floor},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError};
+
+/// The cumulative values accrued while the price was inside a tick range,
+/// returned by the `SnapshotCumulativesInside` instruction.
+///
+/// Only differences between two snapshots of the same range are meaningful,
+/// and only while the range stays initialized in between.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq)]
+pub struct CumulativesInside {
+    /// The tick cumulative accrued inside the range.
+    pub tick_cumulative_inside: i64,
+    /// The seconds per liquidity accrued inside the range, as a Q64.64 number.
+    pub seconds_per_liquidity_inside_x64: u128,
+    /// The seconds spent inside the range.
+    pub seconds_inside: u32,
+}
+
+/// The pool-wide accumulators as of the current operation, which ticks copy
+/// into their "outside" values when initialized or crossed.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GlobalAccumulators {
+    /// The pool's `fee_growth_global_0_x64`.
+    pub fee_growth_global_0_x64: u128,
+    /// The pool's `fee_growth_global_1_x64`.
+    pub fee_growth_global_1_x64: u128,
+    /// The current tick cumulative of the oracle.
+    pub tick_cumulative: i64,
+    /// The current seconds per liquidity cumulative of the oracle.
+    pub seconds_per_liquidity_cumulative_x64: u128,
+    /// The current unix timestamp.
+    pub timestamp: i64,
+    /// The pool's global growth of each reward.
+    pub reward_growths_global_x64: [u128; Pool::NUM_REWARDS],
+}
+
+/// Adds `liquidity_delta` to a tick that bounds a position.
+///
+/// When the tick is initialized by this update, everything is assumed to have
+/// happened below it, so its "outside" values are set to the global values if
+/// the tick is at or below the current tick. When it is uninitialized, it is
+/// cleared with `clear_if_unused`.
+///
+/// # Arguments
+/// * `tick` - The tick to update.
+/// * `tick_index` - The index of the tick.
+/// * `current_tick` - The pool's current tick.
+/// * `liquidity_delta` - The liquidity added (positive) or removed (negative).
+/// * `upper` - Whether the tick is the upper boundary of the position.
+/// * `globals` - The pool-wide accumulators.
+///
+/// # Returns
+/// Whether the tick flipped from uninitialized to initialized or vice versa.
+pub fn update(
+    tick: &mut Tick,
+    tick_index: i32,
+    current_tick: i32,
+    liquidity_delta: i128,
+    upper: bool,
+    globals: &GlobalAccumulators,
+) -> Result<bool, ProgramError> {
+    if tick.liquidity_gross > 0 && tick.tick != tick_index {
+        return Err(SwapV3Error::InvalidTick.into());
+    }
+
+    let liquidity_gross_before = tick.liquidity_gross;
+    let liquidity_gross_after = add_liquidity_delta(liquidity_gross_before, liquidity_delta)?;
+
+    if liquidity_gross_before == 0 {
+        tick.tick = tick_index;
+        if tick_index <= current_tick {
+            tick.fee_growth_outside_0 = globals.fee_growth_global_0_x64;
+            tick.fee_growth_outside_1 = globals.fee_growth_global_1_x64;
+            tick.tick_cumulative_outside = globals.tick_cumulative;
+            tick.seconds_per_liquidity_outside_x64 = globals.seconds_per_liquidity_cumulative_x64;
+            tick.seconds_outside = globals.timestamp as u32;
+            tick.reward_growths_outside_x64 = globals.reward_growths_global_x64;
+        }
+    }
+
+    tick.liquidity_gross = liquidity_gross_after;
+    // Crossing an upper tick from left to right removes the liquidity, so the
+    // net liquidity is subtracted for upper ticks.
+    tick.liquidity = if upper {
+        tick.liquidity.checked_sub(liquidity_delta)
+    } else {
+        tick.liquidity.checked_add(liquidity_delta)
+    }
+    .ok_or(SwapV3Error::Overflow)?;
+    if liquidity_gross_before != 0 {
+        clear_if_unused(tick);
+    }
+
+    Ok((liquidity_gross_after == 0) != (liquidity_gross_before == 0))
+}
+
+/// Transitions to the other side of a tick as the price moves across it,
+/// flipping its "outside" values.
+///
+/// # Arguments
+/// * `tick` - The tick being crossed.
+/// * `globals` - The pool-wide accumulators.
+///
+/// # Returns
+/// The net liquidity to add when crossing from left to right, or subtract when
+/// crossing from right to left.
+pub fn cross(tick: &mut Tick, globals: &GlobalAccumulators) -> i128 {
+    tick.fee_growth_outside_0 = globals
+        .fee_growth_global_0_x64
+        .wrapping_sub(tick.fee_growth_outside_0);
+    tick.fee_growth_outside_1 = globals
+        .fee_growth_global_1_x64
+        .wrapping_sub(tick.fee_growth_outside_1);
+    tick.tick_cumulative_outside = globals
+        .tick_cumulative
+        .wrapping_sub(tick.tick_cumulative_outside);
+    tick.seconds_per_liquidity_outside_x64 = globals
+        .seconds_per_liquidity_cumulative_x64
+        .wrapping_sub(tick.seconds_per_liquidity_outside_x64);
+    tick.seconds_outside = (globals.timestamp as u32).wrapping_sub(tick.seconds_outside);
+    for (outside, global) in tick
+        .reward_growths_outside_x64
+        .iter_mut()
+        .zip(globals.reward_growths_global_x64)
+    {
+        *outside = global.wrapping_sub(*outside);
+    }
+    tick.liquidity
+}
+
+/// Fills the range orders whose lower boundary is `tick`, once the price has
+/// crossed it downwards and the orders are entirely in token_0.
+///
+/// Their liquidity is taken out of the tick's net liquidity, so that it is not
+/// added back if the price moves up again, and the tick's epoch is bumped so
+/// that the orders can be claimed. Both ticks of each order keep its share of
+/// their gross liquidity until the order is claimed with `release_range_order`,
+/// so that neither is uninitialized while the claim depends on it; since swaps
+/// only move the price down, the upper tick is not crossed again in the meantime.
+///
+/// # Returns
+/// The liquidity of the orders that were filled.
+pub fn fill_range_orders(tick: &mut Tick) -> Result<u128, ProgramError> {
+    let filled = tick.range_order_liquidity;
+    if filled == 0 {
+        return Ok(0);
+    }
+    let filled_delta = i128::try_from(filled).map_err(|_| SwapV3Error::Overflow)?;
+
+    tick.liquidity = tick
+        .liquidity
+        .checked_sub(filled_delta)
+        .ok_or(SwapV3Error::Overflow)?;
+    tick.range_order_liquidity = 0;
+    tick.range_order_epoch = tick.range_order_epoch.wrapping_add(1);
+
+    Ok(filled)
+}
+
+/// Removes the liquidity of a filled range order from its ticks.
+///
+/// # Arguments
+/// * `lower` - The lower tick of the range order.
+/// * `upper` - The upper tick of the range order.
+/// * `liquidity` - The liquidity of the range order.
+pub fn release_range_order(
+    lower: &mut Tick,
+    upper: &mut Tick,
+    liquidity: u128,
+) -> Result<(), ProgramError> {
+    let liquidity_delta = i128::try_from(liquidity).map_err(|_| SwapV3Error::Overflow)?;
+
+    // The net liquidity of the lower tick was already removed when the order filled.
+    lower.liquidity_gross = add_liquidity_delta(lower.liquidity_gross, -liquidity_delta)?;
+    upper.liquidity_gross = add_liquidity_delta(upper.liquidity_gross, -liquidity_delta)?;
+    upper.liquidity = upper
+        .liquidity
+        .checked_add(liquidity_delta)
+        .ok_or(SwapV3Error::Overflow)?;
+
+    clear_if_unused(lower);
+    clear_if_unused(upper);
+    Ok(())
+}
+
+/// Uninitializes `tick` once no position uses it as a boundary any more,
+/// resetting its liquidity and "outside" values so that it starts afresh when
+/// it is initialized again. The tick keeps its index, its range order epoch,
+/// which claims of filled orders are compared against, its rent payer, its
+/// layout version, its bump seed and its discriminator.
+///
+/// # Returns
+/// Whether the tick was uninitialized.
+pub fn clear_if_unused(tick: &mut Tick) -> bool {
+    if tick.liquidity_gross != 0 {
+        return false;
+    }
+    *tick = Tick {
+        tick: tick.tick,
+        range_order_epoch: tick.range_order_epoch,
+        rent_payer: tick.rent_payer,
+        version: tick.version,
+        bump: tick.bump,
+        discriminator: tick.discriminator,
+        ..Tick::default()
+    };
+    msg!("Tick {} uninitialized", tick.tick);
+    true
+}
+
+/// Returns the growth of the token_0 and token_1 fees inside the range `[lower, upper)`.
+///
+/// # Arguments
+/// * `lower` - The lower tick of the range.
+/// * `upper` - The upper tick of the range.
+/// * `pool` - The pool, for its current tick and global fee growths.
+pub fn fee_growths_inside(lower: &Tick, upper: &Tick, pool: &Pool) -> (u128, u128) {
+    let current_tick = pool.current_tick;
+    let inside = |global: u128, lower_outside: u128, upper_outside: u128| {
+        let below = if current_tick >= lower.tick {
+            lower_outside
+        } else {
+            global.wrapping_sub(lower_outside)
+        };
+        let above = if current_tick < upper.tick {
+            upper_outside
+        } else {
+            global.wrapping_sub(upper_outside)
+        };
+        global.wrapping_sub(below).wrapping_sub(above)
+    };
+    (
+        inside(
+            pool.fee_growth_global_0_x64,
+            lower.fee_growth_outside_0,
+            upper.fee_growth_outside_0,
+        ),
+        inside(
+            pool.fee_growth_global_1_x64,
+            lower.fee_growth_outside_1,
+            upper.fee_growth_outside_1,
+        ),
+    )
+}
+
+/// Credits a position with the fees its liquidity earned since its last
+/// update. Must be called before the position's liquidity changes.
+///
+/// # Arguments
+/// * `position` - The position to update.
+/// * `fee_growths_inside_x64` - The current token_0 and token_1 fee growth
+///   inside the position's range. See `fee_growths_inside`.
+pub fn update_position_fees(
+    position: &mut Position,
+    fee_growths_inside_x64: (u128, u128),
+) -> Result<(), ProgramError> {
+    for (growth_inside, growth_inside_last, owed) in [
+        (
+            fee_growths_inside_x64.0,
+            &mut position.fee_growth_inside_0_last_x64,
+            &mut position.tokens_owed_0,
+        ),
+        (
+            fee_growths_inside_x64.1,
+            &mut position.fee_growth_inside_1_last_x64,
+            &mut position.tokens_owed_1,
+        ),
+    ] {
+        let growth_delta = growth_inside.wrapping_sub(*growth_inside_last);
+        let earned = mul_div_floor(growth_delta, position.liquidity, Q64)?;
+        *owed = u64::try_from(earned)
+            .ok()
+            .and_then(|earned| owed.checked_add(earned))
+            .ok_or(SwapV3Error::Overflow)?;
+        *growth_inside_last = growth_inside;
+    }
+    Ok(())
+}
+
+/// Returns the cumulative values accrued inside the range `[lower, upper)`.
+///
+/// # Arguments
+/// * `lower` - The initialized lower tick of the range.
+/// * `upper` - The initialized upper tick of the range.
+/// * `current_tick` - The pool's current tick.
+/// * `globals` - The pool-wide accumulators.
+pub fn snapshot_cumulatives_inside(
+    lower: &Tick,
+    upper: &Tick,
+    current_tick: i32,
+    globals: &GlobalAccumulators,
+) -> Result<CumulativesInside, ProgramError> {
+    if lower.liquidity_gross == 0 || upper.liquidity_gross == 0 || lower.tick >= upper.tick {
+        return Err(SwapV3Error::InvalidTick.into());
+    }
+
+    let snapshot = if current_tick < lower.tick {
+        CumulativesInside {
+            tick_cumulative_inside: lower
+                .tick_cumulative_outside
+                .wrapping_sub(upper.tick_cumulative_outside),
+            seconds_per_liquidity_inside_x64: lower
+                .seconds_per_liquidity_outside_x64
+                .wrapping_sub(upper.seconds_per_liquidity_outside_x64),
+            seconds_inside: lower.seconds_outside.wrapping_sub(upper.seconds_outside),
+        }
+    } else if current_tick < upper.tick {
+        CumulativesInside {
+            tick_cumulative_inside: globals
+                .tick_cumulative
+                .wrapping_sub(lower.tick_cumulative_outside)
+                .wrapping_sub(upper.tick_cumulative_outside),
+            seconds_per_liquidity_inside_x64: globals
+                .seconds_per_liquidity_cumulative_x64
+                .wrapping_sub(lower.seconds_per_liquidity_outside_x64)
+                .wrapping_sub(upper.seconds_per_liquidity_outside_x64),
+            seconds_inside: (globals.timestamp as u32)
+                .wrapping_sub(lower.seconds_outside)
+                .wrapping_sub(upper.seconds_outside),
+        }
+    } else {
+        CumulativesInside {
+            tick_cumulative_inside: upper
+                .tick_cumulative_outside
+                .wrapping_sub(lower.tick_cumulative_outside),
+            seconds_per_liquidity_inside_x64: upper
+                .seconds_per_liquidity_outside_x64
+                .wrapping_sub(lower.seconds_per_liquidity_outside_x64),
+            seconds_inside: upper.seconds_outside.wrapping_sub(lower.seconds_outside),
+        }
+    };
+
+    Ok(snapshot)
+}
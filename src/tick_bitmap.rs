@@ -0,0 +1,87 @@
+use crate::{
+    error::SwapV3Error,
+    utils::{MAX_TICK, MIN_TICK},
+};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
+
+/// The number of ticks tracked by each word of the bitmap.
+const WORD_BITS: usize = 64;
+
+/// Returns the word and the bit within it that track `tick`, which must be a
+/// multiple of `tick_spacing` between `MIN_TICK` and `MAX_TICK`.
+///
+/// Bits are counted from the lowest usable tick, so the first bit of the first
+/// word tracks the largest multiple of `tick_spacing` at or below `MIN_TICK`.
+pub fn position(tick: i32, tick_spacing: u16) -> Result<(usize, usize), ProgramError> {
+    let spacing = tick_spacing as i32;
+    if spacing == 0 || tick % spacing != 0 || !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(SwapV3Error::InvalidTick.into());
+    }
+    let index = (tick / spacing - MIN_TICK.div_euclid(spacing)) as usize;
+    Ok((index / WORD_BITS, index % WORD_BITS))
+}
+
+/// Reads the word at `index` of the bitmap.
+fn word(words: &[u8], index: usize) -> Result<u64, ProgramError> {
+    let bytes = words
+        .get(index * 8..index * 8 + 8)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Marks `tick` as initialized or uninitialized in the bitmap.
+///
+/// # Arguments
+/// * `words` - The words of the pool's tick bitmap. See `TickBitmap::words_mut`.
+/// * `tick` - The tick, a multiple of `tick_spacing`.
+/// * `tick_spacing` - The pool's tick spacing.
+/// * `initialized` - Whether the tick's `liquidity_gross` is non-zero.
+pub fn set(words: &mut [u8], tick: i32, tick_spacing: u16, initialized: bool) -> ProgramResult {
+    let (index, bit) = position(tick, tick_spacing)?;
+    let mut value = word(words, index)?;
+    if initialized {
+        value |= 1 << bit;
+    } else {
+        value &= !(1 << bit);
+    }
+    words[index * 8..index * 8 + 8].copy_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+/// Finds the next initialized tick at or below `tick`, searching only the word
+/// that tracks `tick`, as Uniswap v3's `nextInitializedTickWithinOneWord` does
+/// for swaps moving the price down.
+///
+/// When the word tracks no initialized tick at or below `tick`, the lowest tick
+/// the word tracks is returned instead, but no lower than `MIN_TICK`, so that a
+/// swap can step to it and continue its search in the next word.
+///
+/// # Arguments
+/// * `words` - The words of the pool's tick bitmap. See `TickBitmap::words`.
+/// * `tick` - The tick to start from, which need not be a multiple of `tick_spacing`.
+/// * `tick_spacing` - The pool's tick spacing.
+///
+/// # Returns
+/// The tick found, and whether it is initialized.
+pub fn next_initialized_tick_within_one_word(
+    words: &[u8],
+    tick: i32,
+    tick_spacing: u16,
+) -> Result<(i32, bool), ProgramError> {
+    let spacing = tick_spacing as i32;
+    if spacing == 0 {
+        return Err(SwapV3Error::InvalidTick.into());
+    }
+    let first_compressed = MIN_TICK.div_euclid(spacing);
+    let index = (tick.clamp(MIN_TICK, MAX_TICK).div_euclid(spacing) - first_compressed) as usize;
+    let (word_index, bit) = (index / WORD_BITS, index % WORD_BITS);
+
+    let at_or_below = word(words, word_index)? & (u64::MAX >> (WORD_BITS - 1 - bit));
+    let word_start = first_compressed + (word_index * WORD_BITS) as i32;
+    Ok(if at_or_below != 0 {
+        let highest_bit = (WORD_BITS - 1) as i32 - at_or_below.leading_zeros() as i32;
+        ((word_start + highest_bit) * spacing, true)
+    } else {
+        ((word_start * spacing).max(MIN_TICK), false)
+    })
+}
@@ -0,0 +1,284 @@
+use crate::error::SwapV3Error;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey,
+    pubkey::Pubkey,
+    sysvar::{Sysvar, clock::Clock},
+};
+use spl_token::{
+    instruction::TokenInstruction,
+    state::{Account, Mint},
+};
+
+/// The id of the Token-2022 program, whose mints may carry extensions.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// The byte at which Token-2022 stores the account type of an account with
+/// extensions, right after the space of a base token account.
+const ACCOUNT_TYPE_OFFSET: usize = Account::LEN;
+/// The account type Token-2022 uses for mints.
+const ACCOUNT_TYPE_MINT: u8 = 1;
+
+// Token-2022 mint extension types, as numbered by the Token-2022 program.
+const EXTENSION_UNINITIALIZED: u16 = 0;
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const EXTENSION_MINT_CLOSE_AUTHORITY: u16 = 3;
+const EXTENSION_INTEREST_BEARING_CONFIG: u16 = 10;
+const EXTENSION_METADATA_POINTER: u16 = 18;
+const EXTENSION_TOKEN_METADATA: u16 = 19;
+const EXTENSION_GROUP_POINTER: u16 = 20;
+const EXTENSION_TOKEN_GROUP: u16 = 21;
+const EXTENSION_GROUP_MEMBER_POINTER: u16 = 22;
+const EXTENSION_TOKEN_GROUP_MEMBER: u16 = 23;
+const EXTENSION_SCALED_UI_AMOUNT: u16 = 25;
+
+/// The largest transfer fee rate, in basis points.
+const MAX_FEE_BASIS_POINTS: u128 = 10_000;
+
+/// Whether `program_id` is one of the token programs pools can hold tokens of.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == TOKEN_2022_PROGRAM_ID
+}
+
+/// A transfer fee rate of a Token-2022 mint, taking effect from `epoch`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TransferFee {
+    /// The first epoch in which the fee applies.
+    pub epoch: u64,
+    /// The largest fee charged on a single transfer.
+    pub maximum_fee: u64,
+    /// The fee rate, in basis points of the amount sent.
+    pub transfer_fee_basis_points: u16,
+}
+
+/// The parts of a mint that transfers depend on.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MintInfo {
+    /// The number of decimals of the mint, which `TransferChecked` requires.
+    pub decimals: u8,
+    /// The older and newer transfer fees of a Token-2022 mint with the
+    /// transfer fee extension.
+    pub transfer_fees: Option<(TransferFee, TransferFee)>,
+}
+
+impl MintInfo {
+    /// Returns the transfer fee in effect during `epoch`, if any.
+    fn transfer_fee(&self, epoch: u64) -> Option<TransferFee> {
+        self.transfer_fees
+            .map(|(older, newer)| if epoch >= newer.epoch { newer } else { older })
+    }
+
+    /// Returns the fee withheld from a transfer of `amount` during `epoch`, so
+    /// that the destination receives `amount` minus the fee.
+    pub fn fee_on(&self, epoch: u64, amount: u64) -> Result<u64, ProgramError> {
+        let Some(fee) = self.transfer_fee(epoch) else {
+            return Ok(0);
+        };
+        if fee.transfer_fee_basis_points == 0 || amount == 0 {
+            return Ok(0);
+        }
+        // Token-2022 rounds the fee up.
+        let raw_fee =
+            (amount as u128 * fee.transfer_fee_basis_points as u128).div_ceil(MAX_FEE_BASIS_POINTS);
+        Ok(u64::try_from(raw_fee)
+            .map_err(|_| SwapV3Error::Overflow)?
+            .min(fee.maximum_fee))
+    }
+
+    /// Returns the amount to send during `epoch` for the destination to receive `amount`.
+    pub fn amount_to_send(&self, epoch: u64, amount: u64) -> Result<u64, ProgramError> {
+        let Some(fee) = self.transfer_fee(epoch) else {
+            return Ok(amount);
+        };
+        let basis_points = fee.transfer_fee_basis_points as u128;
+        if basis_points == 0 || amount == 0 {
+            return Ok(amount);
+        }
+        let with_maximum_fee = amount
+            .checked_add(fee.maximum_fee)
+            .ok_or(SwapV3Error::Overflow)?;
+        if basis_points >= MAX_FEE_BASIS_POINTS {
+            return Ok(with_maximum_fee);
+        }
+        let with_rate =
+            (amount as u128 * MAX_FEE_BASIS_POINTS).div_ceil(MAX_FEE_BASIS_POINTS - basis_points);
+        Ok(u64::try_from(with_rate)
+            .map_err(|_| SwapV3Error::Overflow)?
+            .min(with_maximum_fee))
+    }
+}
+
+/// Reads a Token or Token-2022 mint, accepting only the Token-2022 extensions
+/// pools are known to hold safely: transfer fees, which transfers account for,
+/// and extensions that leave transfers unchanged, namely a close authority
+/// (which cannot close a mint with supply), metadata and group information,
+/// and the display-only interest-bearing and scaled UI amounts. Any other
+/// extension, including ones added to Token-2022 later, is rejected.
+pub fn unpack_mint(mint: &AccountInfo) -> Result<MintInfo, ProgramError> {
+    if !is_token_program(mint.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = mint.try_borrow_data()?;
+    let base = Mint::unpack(
+        data.get(..Mint::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?,
+    )?;
+    let mut info = MintInfo {
+        decimals: base.decimals,
+        transfer_fees: None,
+    };
+    if data.len() == Mint::LEN {
+        return Ok(info);
+    }
+    if *mint.owner != TOKEN_2022_PROGRAM_ID
+        || data.get(ACCOUNT_TYPE_OFFSET) != Some(&ACCOUNT_TYPE_MINT)
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // The extensions follow as type-length-value entries.
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let extension_type = u16::from_le_bytes([header[0], header[1]]);
+        let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let value = data
+            .get(offset + 4..offset + 4 + length)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match extension_type {
+            EXTENSION_UNINITIALIZED => break,
+            EXTENSION_TRANSFER_FEE_CONFIG => {
+                info.transfer_fees = Some(unpack_transfer_fees(value)?)
+            }
+            EXTENSION_MINT_CLOSE_AUTHORITY
+            | EXTENSION_INTEREST_BEARING_CONFIG
+            | EXTENSION_METADATA_POINTER
+            | EXTENSION_TOKEN_METADATA
+            | EXTENSION_GROUP_POINTER
+            | EXTENSION_TOKEN_GROUP
+            | EXTENSION_GROUP_MEMBER_POINTER
+            | EXTENSION_TOKEN_GROUP_MEMBER
+            | EXTENSION_SCALED_UI_AMOUNT => {}
+            _ => return Err(SwapV3Error::UnsupportedMintExtension.into()),
+        }
+        offset += 4 + length;
+    }
+    Ok(info)
+}
+
+/// Reads the older and newer fees of a `TransferFeeConfig` extension, which
+/// follow its two authorities and its withheld amount.
+fn unpack_transfer_fees(value: &[u8]) -> Result<(TransferFee, TransferFee), ProgramError> {
+    let unpack_fee = |offset: usize| -> Result<TransferFee, ProgramError> {
+        let fee = value
+            .get(offset..offset + 18)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(TransferFee {
+            epoch: u64::from_le_bytes(fee[0..8].try_into().unwrap()),
+            maximum_fee: u64::from_le_bytes(fee[8..16].try_into().unwrap()),
+            transfer_fee_basis_points: u16::from_le_bytes([fee[16], fee[17]]),
+        })
+    };
+    Ok((unpack_fee(72)?, unpack_fee(90)?))
+}
+
+/// Reads a Token or Token-2022 token account, ignoring any extensions.
+pub fn unpack_account(token_account: &AccountInfo) -> Result<Account, ProgramError> {
+    if !is_token_program(token_account.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = token_account.try_borrow_data()?;
+    Account::unpack(
+        data.get(..Account::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?,
+    )
+}
+
+/// A mint and the token program that owns it, which together are all a
+/// transfer of the mint's tokens needs.
+pub struct TokenMint<'a, 'b> {
+    /// The token program that owns the mint.
+    pub program: &'b AccountInfo<'a>,
+    /// The mint account.
+    pub mint: &'b AccountInfo<'a>,
+    /// The parsed mint.
+    pub info: MintInfo,
+    /// The current epoch, which selects the transfer fee in effect.
+    pub epoch: u64,
+}
+
+impl<'a, 'b> TokenMint<'a, 'b> {
+    /// Checks that `mint` is `expected_mint`, owned by `program`, and reads it.
+    pub fn load(
+        program: &'b AccountInfo<'a>,
+        mint: &'b AccountInfo<'a>,
+        expected_mint: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if mint.key != expected_mint {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if program.key != mint.owner {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(Self {
+            program,
+            mint,
+            info: unpack_mint(mint)?,
+            epoch: Clock::get()?.epoch,
+        })
+    }
+
+    /// Returns the amount the destination of a transfer of `amount` receives.
+    pub fn amount_received(&self, amount: u64) -> Result<u64, ProgramError> {
+        Ok(amount - self.info.fee_on(self.epoch, amount)?)
+    }
+
+    /// Returns the amount to send for the destination to receive `amount`.
+    pub fn amount_to_send(&self, amount: u64) -> Result<u64, ProgramError> {
+        self.info.amount_to_send(self.epoch, amount)
+    }
+
+    /// Transfers `amount` tokens with `TransferChecked`, which both token
+    /// programs support, signing with `signer_seeds` when the authority is a PDA.
+    pub fn transfer(
+        &self,
+        source: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        let instruction = Instruction {
+            program_id: *self.program.key,
+            accounts: vec![
+                AccountMeta::new(*source.key, false),
+                AccountMeta::new_readonly(*self.mint.key, false),
+                AccountMeta::new(*destination.key, false),
+                AccountMeta::new_readonly(*authority.key, true),
+            ],
+            data: TokenInstruction::TransferChecked {
+                amount,
+                decimals: self.info.decimals,
+            }
+            .pack(),
+        };
+        invoke_signed(
+            &instruction,
+            &[
+                source.clone(),
+                self.mint.clone(),
+                destination.clone(),
+                authority.clone(),
+                self.program.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+}
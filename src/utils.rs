@@ -1,115 +1,928 @@
+use crate::big_num::{U256, U512};
 use crate::error::SwapV3Error;
-use num_bigint::BigUint;
-use num_traits::{ToPrimitive, Zero};
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::program_error::ProgramError;
+use std::{fmt, str::FromStr};
+
+/// The Q64.64 fixed-point representation of 1.0.
+pub const Q64: u128 = 1 << 64;
+
+/// The number of decimal digits needed to print the fractional part of a
+/// Q64.64 number exactly (2^-64 = 5^64 / 10^64).
+const Q64_FRACTION_DIGITS: usize = 64;
+
+/// The most fractional decimal digits `Q64x64::from_str` takes into account;
+/// 10^38 still fits in a u128, and 10^-38 is far below the 2^-64 resolution.
+const Q64_PARSE_DIGITS: usize = 38;
+
+/// An unsigned Q64.64 fixed-point number: 64 integer bits and 64 fractional bits.
+#[derive(
+    BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub struct Q64x64(u128);
+
+impl Q64x64 {
+    /// The value 0.
+    pub const ZERO: Self = Self(0);
+    /// The value 1.
+    pub const ONE: Self = Self(Q64);
+
+    /// Creates a number from its raw Q64.64 bits.
+    pub const fn from_bits(bits: u128) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw Q64.64 bits of the number.
+    pub const fn to_bits(self) -> u128 {
+        self.0
+    }
+
+    /// Adds two numbers, returning `None` on overflow.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Subtracts two numbers, returning `None` on underflow.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Multiplies two numbers, rounding down and returning `None` on overflow.
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        mul_div_floor(self.0, other.0, Q64).ok().map(Self)
+    }
+
+    /// Divides two numbers, rounding down and returning `None` on overflow
+    /// or division by zero.
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        mul_div_floor(self.0, Q64, other.0).ok().map(Self)
+    }
+
+    /// Converts an `f64` into the nearest representable number below it, returning
+    /// `None` for negative, non-finite or too large values.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let bits = value * Q64 as f64;
+        if !bits.is_finite() || bits < 0.0 || bits >= u128::MAX as f64 {
+            return None;
+        }
+        Some(Self(bits as u128))
+    }
+
+    /// Converts the number into an `f64`, losing precision beyond 53 significant bits.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Q64 as f64
+    }
+}
+
+/// Formats the number as an exact decimal string, without trailing zeros.
+impl fmt::Display for Q64x64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integer = self.0 >> 64;
+        let fraction = self.0 as u64;
+        if fraction == 0 {
+            return write!(f, "{}", integer);
+        }
+
+        // fraction / 2^64 == fraction * 5^64 / 10^64
+        let digits = U256::from(fraction) * U256::from(5u8).pow(U256::from(64u8));
+        let digits = format!("{:0>width$}", digits, width = Q64_FRACTION_DIGITS);
+        write!(f, "{}.{}", integer, digits.trim_end_matches('0'))
+    }
+}
+
+/// Parses a decimal string such as `"1.5"`, rounding down to the nearest
+/// representable number.
+impl FromStr for Q64x64 {
+    type Err = SwapV3Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+            return Err(SwapV3Error::InvalidFixedPoint);
+        }
+
+        let integer: u64 = integer
+            .parse()
+            .map_err(|_| SwapV3Error::InvalidFixedPoint)?;
+        let fraction = &fraction[..fraction.len().min(Q64_PARSE_DIGITS)];
+        let fraction_bits = if fraction.is_empty() {
+            0
+        } else {
+            let numerator: u128 = fraction
+                .parse()
+                .map_err(|_| SwapV3Error::InvalidFixedPoint)?;
+            let denominator = 10u128.pow(fraction.len() as u32);
+            mul_div_floor(numerator, Q64, denominator)
+                .map_err(|_| SwapV3Error::InvalidFixedPoint)?
+        };
+
+        Ok(Self(((integer as u128) << 64) | fraction_bits))
+    }
+}
+
+/// The square root of a price (token_1 per token_0), stored as a Q64.64 number.
+///
+/// Keeping sqrt prices in their own type prevents them from being mixed up with
+/// prices, liquidity or token amounts, which are all plain integers otherwise.
+#[derive(
+    BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub struct SqrtPriceX64(Q64x64);
+
+impl SqrtPriceX64 {
+    /// Creates a sqrt price from its raw Q64.64 bits.
+    pub const fn from_bits(bits: u128) -> Self {
+        Self(Q64x64::from_bits(bits))
+    }
+
+    /// Returns the raw Q64.64 bits of the sqrt price.
+    pub const fn to_bits(self) -> u128 {
+        self.0.to_bits()
+    }
+
+    /// Returns the sqrt price as a plain Q64.64 number.
+    pub const fn as_q64x64(self) -> Q64x64 {
+        self.0
+    }
+
+    /// Creates a sqrt price from a price expressed as an `f64`.
+    pub fn from_price_f64(price: f64) -> Option<Self> {
+        // Negative prices become NaN here, which `from_f64` rejects.
+        Q64x64::from_f64(price.sqrt()).map(Self)
+    }
+
+    /// Returns the price (the square of the sqrt price) as an `f64`.
+    pub fn to_price_f64(self) -> f64 {
+        let sqrt_price = self.0.to_f64();
+        sqrt_price * sqrt_price
+    }
+}
+
+impl From<Q64x64> for SqrtPriceX64 {
+    fn from(value: Q64x64) -> Self {
+        Self(value)
+    }
+}
+
+/// Formats the sqrt price as an exact decimal string.
+impl fmt::Display for SqrtPriceX64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Parses a sqrt price from a decimal string.
+impl FromStr for SqrtPriceX64 {
+    type Err = SwapV3Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+/// The minimum tick index supported by the pool.
+///
+/// Ticks are bounded so that the price 1.0001^tick stays within [2^-64, 2^64],
+/// i.e. the price itself (not just its square root) fits in a Q64.64 number:
+/// 443636 = floor(log_1.0001(2^64)).
+pub const MIN_TICK: i32 = -443636;
+/// The maximum tick index supported by the pool. See `MIN_TICK`.
+pub const MAX_TICK: i32 = -MIN_TICK;
+
+/// The sqrt price at `MIN_TICK`, i.e. `get_sqrt_ratio_at_tick(MIN_TICK)`.
+/// This is the lowest sqrt price a pool can reach.
+pub const MIN_SQRT_RATIO: SqrtPriceX64 = SqrtPriceX64::from_bits(4295048017);
+/// The sqrt price at `MAX_TICK`, i.e. `get_sqrt_ratio_at_tick(MAX_TICK)`.
+/// Pool prices must stay strictly below this value.
+pub const MAX_SQRT_RATIO: SqrtPriceX64 = SqrtPriceX64::from_bits(79226673515401279992447579062);
 
 /// Calculates the square root of the price for a given tick index.
 /// The price is represented as a Q64.64 fixed-point number.
 ///
 /// Formula: sqrt(p) = 1.0001^(tick / 2) * 2^64
 ///
+/// The computation is carried out in Q128.128 and rounded up to Q64.64 at the end,
+/// so the result is never below the exact sqrt price and, for every tick, at most
+/// two Q64.64 units above it.
+///
 /// # Arguments
-/// * `tick` - The tick index.
+/// * `tick` - The tick index, in `MIN_TICK..=MAX_TICK`.
 ///
 /// # Returns
-/// The square root of the price as a u128.
-pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<u128, ProgramError> {
-    let tick_abs = tick.abs();
-    if tick_abs > 443636 {
-        // A reasonable limit to prevent overflow
+/// The square root of the price.
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<SqrtPriceX64, ProgramError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
         return Err(SwapV3Error::InvalidTick.into());
     }
+    let tick_abs = tick.unsigned_abs();
 
-    let mut ratio: BigUint = if (tick_abs & 0x1) != 0 {
-        BigUint::from(0xfffcb933bd6a8861u128)
+    // Each constant is 1.0001^(-2^i / 2) as a Q128.128 number, rounded to nearest,
+    // so the product of the constants for the set bits of |tick| is sqrt(1.0001^-|tick|).
+    // The ratio never exceeds 2^128 and every constant is below 2^128, so the
+    // products fit in a U256.
+    let mut ratio = if (tick_abs & 0x1) != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
     } else {
-        BigUint::from(0x10000000000000000u128)
+        U256::one() << 128
     };
 
     if (tick_abs & 0x2) != 0 {
-        ratio = (ratio * BigUint::from(0xfff97272373d4131u128)) >> 128;
+        ratio = (ratio * U256::from(0xfff97272373d413259a46990580e213au128)) >> 128;
     }
     if (tick_abs & 0x4) != 0 {
-        ratio = (ratio * BigUint::from(0xfff2e50f5f656932u128)) >> 128;
+        ratio = (ratio * U256::from(0xfff2e50f5f656932ef12357cf3c7fdccu128)) >> 128;
     }
     if (tick_abs & 0x8) != 0 {
-        ratio = (ratio * BigUint::from(0xffe5caca7e10e56fu128)) >> 128;
+        ratio = (ratio * U256::from(0xffe5caca7e10e4e61c3624eaa0941cd0u128)) >> 128;
     }
     if (tick_abs & 0x10) != 0 {
-        ratio = (ratio * BigUint::from(0xffcb9843d60f6159u128)) >> 128;
+        ratio = (ratio * U256::from(0xffcb9843d60f6159c9db58835c926644u128)) >> 128;
     }
     if (tick_abs & 0x20) != 0 {
-        ratio = (ratio * BigUint::from(0xff973b41fa98c08eu128)) >> 128;
+        ratio = (ratio * U256::from(0xff973b41fa98c081472e6896dfb254c0u128)) >> 128;
     }
     if (tick_abs & 0x40) != 0 {
-        ratio = (ratio * BigUint::from(0xff2ea16466c96a38u128)) >> 128;
+        ratio = (ratio * U256::from(0xff2ea16466c96a3843ec78b326b52861u128)) >> 128;
     }
     if (tick_abs & 0x80) != 0 {
-        ratio = (ratio * BigUint::from(0xfe5c563584e73a7bu128)) >> 128;
+        ratio = (ratio * U256::from(0xfe5dee046a99a2a811c461f1969c3053u128)) >> 128;
     }
     if (tick_abs & 0x100) != 0 {
-        ratio = (ratio * BigUint::from(0xfcbe86c7900a88a8u128)) >> 128;
+        ratio = (ratio * U256::from(0xfcbe86c7900a88aedcffc83b479aa3a4u128)) >> 128;
     }
     if (tick_abs & 0x200) != 0 {
-        ratio = (ratio * BigUint::from(0xf987a7253ac41317u128)) >> 128;
+        ratio = (ratio * U256::from(0xf987a7253ac413176f2b074cf7815e54u128)) >> 128;
     }
     if (tick_abs & 0x400) != 0 {
-        ratio = (ratio * BigUint::from(0xf30ee3e2198a2598u128)) >> 128;
+        ratio = (ratio * U256::from(0xf3392b0822b70005940c7a398e4b70f3u128)) >> 128;
     }
     if (tick_abs & 0x800) != 0 {
-        ratio = (ratio * BigUint::from(0xe69594ea4a695914u128)) >> 128;
+        ratio = (ratio * U256::from(0xe7159475a2c29b7443b29c7fa6e889d9u128)) >> 128;
     }
     if (tick_abs & 0x1000) != 0 {
-        ratio = (ratio * BigUint::from(0xcf0569513744055bu128)) >> 128;
+        ratio = (ratio * U256::from(0xd097f3bdfd2022b8845ad8f792aa5825u128)) >> 128;
     }
     if (tick_abs & 0x2000) != 0 {
-        ratio = (ratio * BigUint::from(0x9e774231edb50266u128)) >> 128;
+        ratio = (ratio * U256::from(0xa9f746462d870fdf8a65dc1f90e061e5u128)) >> 128;
     }
     if (tick_abs & 0x4000) != 0 {
-        ratio = (ratio * BigUint::from(0x6675fd95340d4576u128)) >> 128;
+        ratio = (ratio * U256::from(0x70d869a156d2a1b890bb3df62baf32f7u128)) >> 128;
     }
     if (tick_abs & 0x8000) != 0 {
-        ratio = (ratio * BigUint::from(0x3202b17ecdf93341u128)) >> 128;
+        ratio = (ratio * U256::from(0x31be135f97d08fd981231505542fcfa6u128)) >> 128;
     }
     if (tick_abs & 0x10000) != 0 {
-        ratio = (ratio * BigUint::from(0xa93819b068533333u128)) >> 128;
+        ratio = (ratio * U256::from(0x9aa508b5b7a84e1c677de54f3e99bc9u128)) >> 128;
     }
     if (tick_abs & 0x20000) != 0 {
-        ratio = (ratio * BigUint::from(0x5555555555555555u128)) >> 128;
+        ratio = (ratio * U256::from(0x5d6af8dedb81196699c329225ee604u128)) >> 128;
+    }
+    if (tick_abs & 0x40000) != 0 {
+        ratio = (ratio * U256::from(0x2216e584f5fa1ea926041bedfe98u128)) >> 128;
     }
 
     if tick > 0 {
         // Invert the ratio for positive ticks
-        let ratio_big: BigUint = (BigUint::from(1u128) << 256) / ratio;
-        Ok(ratio_big.to_u128().unwrap_or(0))
+        ratio = U256::MAX / ratio;
+    }
+
+    // Convert from Q128.128 to Q64.64, rounding up.
+    let remainder = ratio.low_u64();
+    let ratio = (ratio >> 64) + if remainder == 0 { 0 } else { 1 };
+    let ratio = u128::try_from(ratio).map_err(|_| SwapV3Error::Overflow)?;
+
+    Ok(SqrtPriceX64::from_bits(ratio))
+}
+
+/// The number of fractional bits of log2(sqrt_price) computed by `get_tick_at_sqrt_ratio`.
+const LOG2_FRACTION_BITS: u32 = 14;
+
+/// 1 / log2(sqrt(1.0001)) as a Q32.32 number, converting a log2 into a tick index.
+const LOG_SQRT_10001_INV_X32: i128 = 59543866431248;
+
+/// A 0.01 tick safety margin below the estimate, as a Q64.64 number.
+const TICK_ESTIMATE_MARGIN_LOWER_X64: i128 = 184467440737095516;
+
+/// The truncation error of the fractional log2 bits (2^-14 / log2(sqrt(1.0001)))
+/// plus a 0.01 tick safety margin above the estimate, as a Q64.64 number.
+const TICK_ESTIMATE_MARGIN_UPPER_X64: i128 = 15793534762490258745;
+
+/// Calculates the greatest tick index whose sqrt price is less than or equal
+/// to the given sqrt price, i.e. the inverse of `get_sqrt_ratio_at_tick`.
+///
+/// The tick is estimated from log2(sqrt_price), which narrows the result down to
+/// at most two candidates; the exact floor is then decided by comparing against
+/// `get_sqrt_ratio_at_tick`, so the two functions always round-trip.
+///
+/// # Arguments
+/// * `sqrt_price` - The sqrt price, in `MIN_SQRT_RATIO..MAX_SQRT_RATIO`.
+///
+/// # Returns
+/// The tick index as an i32.
+pub fn get_tick_at_sqrt_ratio(sqrt_price: SqrtPriceX64) -> Result<i32, ProgramError> {
+    if !(MIN_SQRT_RATIO..MAX_SQRT_RATIO).contains(&sqrt_price) {
+        return Err(SwapV3Error::InvalidSqrtPrice.into());
+    }
+    let sqrt_price_x64 = sqrt_price.to_bits();
+
+    // Integer part of log2: the position of the most significant bit, relative to 2^64.
+    let msb = 127 - sqrt_price_x64.leading_zeros();
+    let log2_integer_x32 = (msb as i128 - 64) << 32;
+
+    // Normalize the price into [1, 2) as a Q1.63 number.
+    let mut r = if msb >= 63 {
+        sqrt_price_x64 >> (msb - 63)
+    } else {
+        sqrt_price_x64 << (63 - msb)
+    };
+
+    // Fractional part of log2 by repeated squaring: each time r^2 >= 2,
+    // the current fractional bit is set and r is halved.
+    let mut log2_fraction_x64: i128 = 0;
+    let mut bit: i128 = 1 << 63;
+    for _ in 0..LOG2_FRACTION_BITS {
+        r *= r;
+        let overflow = (r >> 127) as u32;
+        r >>= 63 + overflow;
+        if overflow != 0 {
+            log2_fraction_x64 |= bit;
+        }
+        bit >>= 1;
+    }
+
+    let log2_x32 = log2_integer_x32 + (log2_fraction_x64 >> 32);
+    let log_sqrt_10001_x64 = log2_x32 * LOG_SQRT_10001_INV_X32;
+
+    let tick_low = ((log_sqrt_10001_x64 - TICK_ESTIMATE_MARGIN_LOWER_X64) >> 64) as i32;
+    let tick_high = ((log_sqrt_10001_x64 + TICK_ESTIMATE_MARGIN_UPPER_X64) >> 64) as i32;
+
+    if tick_low == tick_high || get_sqrt_ratio_at_tick(tick_high)? > sqrt_price {
+        Ok(tick_low)
     } else {
-        Ok(ratio.to_u128().unwrap_or(0))
+        Ok(tick_high)
     }
 }
 
-/// Performs a multiplication and division operation: (a * b) / c
-/// This is implemented using BigUint to prevent overflow.
+/// The direction in which the result of a division is rounded.
+///
+/// Amounts the pool pays out are rounded `Down` and amounts it takes in are
+/// rounded `Up`, so rounding errors always accrue to the pool and the vaults
+/// stay solvent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round towards zero.
+    Down,
+    /// Round away from zero.
+    Up,
+}
+
+/// Performs a multiplication and division operation: (a * b) / c.
+/// The product is computed as a fixed-width U256 to prevent overflow.
 ///
 /// # Arguments
 /// * `a`, `b`, `c` - The numbers to operate on.
+/// * `rounding` - The direction in which to round the quotient.
 ///
 /// # Returns
 /// The result of the operation as a u128.
-pub fn mul_div(a: u128, b: u128, c: u128) -> Result<u128, ProgramError> {
-    let a_big = BigUint::from(a);
-    let b_big = BigUint::from(b);
-    let c_big = BigUint::from(c);
-
-    if c_big.is_zero() {
+pub fn mul_div(a: u128, b: u128, c: u128, rounding: Rounding) -> Result<u128, ProgramError> {
+    if c == 0 {
         return Err(SwapV3Error::Overflow.into());
     }
 
-    let result_big = (a_big * b_big) / c_big;
+    let (quotient, remainder) = (U256::from(a) * U256::from(b)).div_mod(U256::from(c));
+    let quotient = if rounding == Rounding::Up && !remainder.is_zero() {
+        quotient + 1
+    } else {
+        quotient
+    };
+
+    u128::try_from(quotient).map_err(|_| SwapV3Error::Overflow.into())
+}
+
+/// Performs a multiplication and division operation: (a * b) / c, rounding down.
+pub fn mul_div_floor(a: u128, b: u128, c: u128) -> Result<u128, ProgramError> {
+    mul_div(a, b, c, Rounding::Down)
+}
 
-    // Check if the result fits within a u128
-    if result_big.bits() > 128 {
+/// Performs a multiplication and division operation: (a * b) / c, rounding up.
+pub fn mul_div_rounding_up(a: u128, b: u128, c: u128) -> Result<u128, ProgramError> {
+    mul_div(a, b, c, Rounding::Up)
+}
+
+/// Divides `a` by `b`, rounding up.
+///
+/// # Returns
+/// The quotient as a u128, or an error if `b` is zero.
+pub fn div_rounding_up(a: u128, b: u128) -> Result<u128, ProgramError> {
+    if b == 0 {
         return Err(SwapV3Error::Overflow.into());
     }
+    Ok(a.div_ceil(b))
+}
+
+/// Divides two U512 values, rounding the quotient in the given direction.
+fn div_u512(numerator: U512, denominator: U512, rounding: Rounding) -> U512 {
+    let (quotient, remainder) = numerator.div_mod(denominator);
+    if rounding == Rounding::Up && !remainder.is_zero() {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+/// Calculates the amount of token_0 that corresponds to `liquidity` between two sqrt prices.
+///
+/// Formula: amount0 = liquidity * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)
+///
+/// # Arguments
+/// * `sqrt_ratio_a`, `sqrt_ratio_b` - The two sqrt prices, in either order.
+/// * `liquidity` - The liquidity amount.
+/// * `rounding` - `Up` for amounts paid into the pool, `Down` for amounts paid out.
+///
+/// # Returns
+/// The amount of token_0 as a u128.
+pub fn get_amount0_delta(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    liquidity: u128,
+    rounding: Rounding,
+) -> Result<u128, ProgramError> {
+    let (sqrt_lower, sqrt_upper) = sorted_sqrt_price_bits(sqrt_ratio_a, sqrt_ratio_b);
+    if sqrt_lower == 0 {
+        return Err(SwapV3Error::InvalidSqrtPrice.into());
+    }
+
+    let numerator = (U512::from(liquidity) << 64) * U512::from(sqrt_upper - sqrt_lower);
+    let amount = div_u512(
+        div_u512(numerator, U512::from(sqrt_upper), rounding),
+        U512::from(sqrt_lower),
+        rounding,
+    );
+
+    u128::try_from(amount).map_err(|_| SwapV3Error::Overflow.into())
+}
+
+/// Calculates the amount of token_1 that corresponds to `liquidity` between two sqrt prices.
+///
+/// Formula: amount1 = liquidity * (sqrt_b - sqrt_a)
+///
+/// # Arguments
+/// * `sqrt_ratio_a`, `sqrt_ratio_b` - The two sqrt prices, in either order.
+/// * `liquidity` - The liquidity amount.
+/// * `rounding` - `Up` for amounts paid into the pool, `Down` for amounts paid out.
+///
+/// # Returns
+/// The amount of token_1 as a u128.
+pub fn get_amount1_delta(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    liquidity: u128,
+    rounding: Rounding,
+) -> Result<u128, ProgramError> {
+    let sqrt_diff = sqrt_ratio_a.to_bits().abs_diff(sqrt_ratio_b.to_bits());
+
+    mul_div(liquidity, sqrt_diff, Q64, rounding)
+}
+
+/// Calculates the signed token_0 delta for a signed liquidity change.
+///
+/// Adding liquidity (positive) rounds up, since the pool receives the tokens;
+/// removing liquidity (negative) rounds down, since the pool pays them out.
+///
+/// # Returns
+/// The amount of token_0 as an i128, negative when the pool pays out.
+pub fn get_amount0_delta_signed(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    liquidity: i128,
+) -> Result<i128, ProgramError> {
+    let amount = get_amount0_delta(
+        sqrt_ratio_a,
+        sqrt_ratio_b,
+        liquidity.unsigned_abs(),
+        rounding_for_liquidity(liquidity),
+    )?;
+    signed_amount(amount, liquidity < 0)
+}
+
+/// Calculates the signed token_1 delta for a signed liquidity change.
+///
+/// Rounds the same way as `get_amount0_delta_signed`.
+///
+/// # Returns
+/// The amount of token_1 as an i128, negative when the pool pays out.
+pub fn get_amount1_delta_signed(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    liquidity: i128,
+) -> Result<i128, ProgramError> {
+    let amount = get_amount1_delta(
+        sqrt_ratio_a,
+        sqrt_ratio_b,
+        liquidity.unsigned_abs(),
+        rounding_for_liquidity(liquidity),
+    )?;
+    signed_amount(amount, liquidity < 0)
+}
+
+/// Returns the rounding for the token amounts of a signed liquidity change:
+/// deposits (positive) round up and withdrawals (negative) round down.
+fn rounding_for_liquidity(liquidity: i128) -> Rounding {
+    if liquidity >= 0 {
+        Rounding::Up
+    } else {
+        Rounding::Down
+    }
+}
+
+/// Returns the raw bits of two sqrt prices, ordered from lower to upper.
+fn sorted_sqrt_price_bits(a: SqrtPriceX64, b: SqrtPriceX64) -> (u128, u128) {
+    if a <= b {
+        (a.to_bits(), b.to_bits())
+    } else {
+        (b.to_bits(), a.to_bits())
+    }
+}
+
+/// Converts an unsigned token amount into a signed delta.
+fn signed_amount(amount: u128, negative: bool) -> Result<i128, ProgramError> {
+    let amount = i128::try_from(amount).map_err(|_| SwapV3Error::Overflow)?;
+    Ok(if negative { -amount } else { amount })
+}
+
+/// Calculates the liquidity received for a given amount of token_0 over a price range.
+///
+/// Formula: liquidity = amount0 * (sqrt_a * sqrt_b) / (sqrt_b - sqrt_a)
+///
+/// # Arguments
+/// * `sqrt_ratio_a`, `sqrt_ratio_b` - The range boundaries, in either order.
+/// * `amount_0` - The amount of token_0 being deposited.
+///
+/// # Returns
+/// The liquidity as a u128, rounded down.
+pub fn get_liquidity_for_amount0(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    amount_0: u128,
+) -> Result<u128, ProgramError> {
+    let (sqrt_lower, sqrt_upper) = sorted_sqrt_price_bits(sqrt_ratio_a, sqrt_ratio_b);
+
+    let intermediate = mul_div_floor(sqrt_lower, sqrt_upper, Q64)?;
+    mul_div_floor(amount_0, intermediate, sqrt_upper - sqrt_lower)
+}
+
+/// Calculates the liquidity received for a given amount of token_1 over a price range.
+///
+/// Formula: liquidity = amount1 / (sqrt_b - sqrt_a)
+///
+/// # Arguments
+/// * `sqrt_ratio_a`, `sqrt_ratio_b` - The range boundaries, in either order.
+/// * `amount_1` - The amount of token_1 being deposited.
+///
+/// # Returns
+/// The liquidity as a u128, rounded down.
+pub fn get_liquidity_for_amount1(
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    amount_1: u128,
+) -> Result<u128, ProgramError> {
+    let sqrt_diff = sqrt_ratio_a.to_bits().abs_diff(sqrt_ratio_b.to_bits());
+    mul_div_floor(amount_1, Q64, sqrt_diff)
+}
+
+/// Calculates the maximum liquidity that can be minted from the given token amounts
+/// for a range, given the pool's current sqrt price.
+///
+/// Below the range only token_0 is needed, above it only token_1, and inside it the
+/// smaller of the two per-token liquidities is used.
+///
+/// # Arguments
+/// * `sqrt_price` - The current sqrt price of the pool.
+/// * `sqrt_ratio_a`, `sqrt_ratio_b` - The range boundaries, in either order.
+/// * `amount_0`, `amount_1` - The maximum token amounts being deposited.
+///
+/// # Returns
+/// The liquidity as a u128, rounded down.
+pub fn get_liquidity_for_amounts(
+    sqrt_price: SqrtPriceX64,
+    sqrt_ratio_a: SqrtPriceX64,
+    sqrt_ratio_b: SqrtPriceX64,
+    amount_0: u128,
+    amount_1: u128,
+) -> Result<u128, ProgramError> {
+    let (sqrt_lower, sqrt_upper) = if sqrt_ratio_a <= sqrt_ratio_b {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    } else {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    };
+
+    if sqrt_price <= sqrt_lower {
+        get_liquidity_for_amount0(sqrt_lower, sqrt_upper, amount_0)
+    } else if sqrt_price < sqrt_upper {
+        let liquidity_0 = get_liquidity_for_amount0(sqrt_price, sqrt_upper, amount_0)?;
+        let liquidity_1 = get_liquidity_for_amount1(sqrt_lower, sqrt_price, amount_1)?;
+        Ok(liquidity_0.min(liquidity_1))
+    } else {
+        get_liquidity_for_amount1(sqrt_lower, sqrt_upper, amount_1)
+    }
+}
+
+/// Adds a signed liquidity delta to a liquidity amount.
+///
+/// # Returns
+/// The new liquidity, or an `Overflow` error if it would leave the u128 range.
+pub fn add_liquidity_delta(liquidity: u128, delta: i128) -> Result<u128, ProgramError> {
+    if delta >= 0 {
+        liquidity.checked_add(delta as u128)
+    } else {
+        liquidity.checked_sub(delta.unsigned_abs())
+    }
+    .ok_or_else(|| SwapV3Error::Overflow.into())
+}
+
+/// The denominator of `Pool.fee`: fees are expressed in hundredths of a basis point.
+pub const FEE_RATE_DENOMINATOR: u32 = 1_000_000;
+
+/// Calculates the next sqrt price after adding or removing `amount` of token_0.
+///
+/// Formula: sqrt_next = liquidity * sqrt_p / (liquidity +/- amount * sqrt_p)
+///
+/// The result is always rounded up, so that moving the price down (adding token_0)
+/// never goes further than the amount paid in allows, and moving it up (removing
+/// token_0) always goes at least far enough to cover the amount paid out.
+fn get_next_sqrt_price_from_amount0_rounding_up(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u128,
+    add: bool,
+) -> Result<u128, ProgramError> {
+    if amount == 0 {
+        return Ok(sqrt_price_x64);
+    }
+
+    let numerator = U512::from(liquidity) << 64;
+    let product = U512::from(amount) * U512::from(sqrt_price_x64);
+    let denominator = if add {
+        numerator + product
+    } else {
+        if product >= numerator {
+            return Err(SwapV3Error::InvalidPriceLimit.into());
+        }
+        numerator - product
+    };
+
+    let next = div_u512(
+        numerator * U512::from(sqrt_price_x64),
+        denominator,
+        Rounding::Up,
+    );
+    u128::try_from(next).map_err(|_| SwapV3Error::Overflow.into())
+}
+
+/// Calculates the next sqrt price after adding or removing `amount` of token_1.
+///
+/// Formula: sqrt_next = sqrt_p +/- amount / liquidity
+///
+/// The result is always rounded down, for the same reason `get_next_sqrt_price_from_amount0_rounding_up`
+/// rounds up: the pool never gives away more than it is paid for.
+fn get_next_sqrt_price_from_amount1_rounding_down(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u128,
+    add: bool,
+) -> Result<u128, ProgramError> {
+    if add {
+        let quotient = mul_div_floor(amount, Q64, liquidity)?;
+        sqrt_price_x64
+            .checked_add(quotient)
+            .ok_or_else(|| SwapV3Error::Overflow.into())
+    } else {
+        let quotient = mul_div_rounding_up(amount, Q64, liquidity)?;
+        if quotient >= sqrt_price_x64 {
+            return Err(SwapV3Error::InvalidPriceLimit.into());
+        }
+        Ok(sqrt_price_x64 - quotient)
+    }
+}
+
+/// Calculates the next sqrt price given an input amount of token_0 or token_1.
+///
+/// # Arguments
+/// * `sqrt_price` - The current sqrt price.
+/// * `liquidity` - The active liquidity.
+/// * `amount_in` - The amount of the input token.
+/// * `zero_for_one` - Whether the input token is token_0 (price moves down) or token_1 (price moves up).
+///
+/// # Returns
+/// The next sqrt price.
+pub fn get_next_sqrt_price_from_input(
+    sqrt_price: SqrtPriceX64,
+    liquidity: u128,
+    amount_in: u128,
+    zero_for_one: bool,
+) -> Result<SqrtPriceX64, ProgramError> {
+    let sqrt_price_x64 = sqrt_price.to_bits();
+    if sqrt_price_x64 == 0 {
+        return Err(SwapV3Error::InvalidSqrtPrice.into());
+    }
+    if liquidity == 0 {
+        return Err(SwapV3Error::ZeroLiquidity.into());
+    }
+
+    let next = if zero_for_one {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_price_x64, liquidity, amount_in, true)?
+    } else {
+        get_next_sqrt_price_from_amount1_rounding_down(sqrt_price_x64, liquidity, amount_in, true)?
+    };
+    Ok(SqrtPriceX64::from_bits(next))
+}
+
+/// Calculates the next sqrt price given an output amount of token_0 or token_1.
+///
+/// # Arguments
+/// * `sqrt_price` - The current sqrt price.
+/// * `liquidity` - The active liquidity.
+/// * `amount_out` - The amount of the output token.
+/// * `zero_for_one` - Whether the output token is token_1 (price moves down) or token_0 (price moves up).
+///
+/// # Returns
+/// The next sqrt price.
+pub fn get_next_sqrt_price_from_output(
+    sqrt_price: SqrtPriceX64,
+    liquidity: u128,
+    amount_out: u128,
+    zero_for_one: bool,
+) -> Result<SqrtPriceX64, ProgramError> {
+    let sqrt_price_x64 = sqrt_price.to_bits();
+    if sqrt_price_x64 == 0 {
+        return Err(SwapV3Error::InvalidSqrtPrice.into());
+    }
+    if liquidity == 0 {
+        return Err(SwapV3Error::ZeroLiquidity.into());
+    }
+
+    let next = if zero_for_one {
+        get_next_sqrt_price_from_amount1_rounding_down(
+            sqrt_price_x64,
+            liquidity,
+            amount_out,
+            false,
+        )?
+    } else {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_price_x64, liquidity, amount_out, false)?
+    };
+    Ok(SqrtPriceX64::from_bits(next))
+}
+
+/// Computes the result of swapping within a single liquidity range, moving the price
+/// from `sqrt_price_current` towards `sqrt_price_target`.
+///
+/// The direction of the swap is implied by the two prices: token_0 is sold when the
+/// target is below the current price. Input amounts and fees round up and output
+/// amounts round down.
+///
+/// # Arguments
+/// * `sqrt_price_current` - The current sqrt price.
+/// * `sqrt_price_target` - The price that cannot be exceeded in this step.
+/// * `liquidity` - The active liquidity.
+/// * `amount_remaining` - The amount still to be swapped.
+/// * `exact_input` - Whether `amount_remaining` is an input (including fees) or an output amount.
+/// * `fee` - The fee rate, in units of `FEE_RATE_DENOMINATOR`.
+///
+/// # Returns
+/// `(sqrt_price_next, amount_in, amount_out, fee_amount)`, where `amount_in` excludes the fee.
+pub fn compute_swap_step(
+    sqrt_price_current: SqrtPriceX64,
+    sqrt_price_target: SqrtPriceX64,
+    liquidity: u128,
+    amount_remaining: u128,
+    exact_input: bool,
+    fee: u32,
+) -> Result<(SqrtPriceX64, u128, u128, u128), ProgramError> {
+    if fee >= FEE_RATE_DENOMINATOR {
+        return Err(SwapV3Error::InvalidInstruction.into());
+    }
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let fee = fee as u128;
+    let fee_complement = (FEE_RATE_DENOMINATOR as u128) - fee;
+
+    // The amounts needed to reach the target price; only the one matching
+    // `exact_input` is computed up front.
+    let mut amount_in_to_target = 0;
+    let mut amount_out_to_target = 0;
+
+    let sqrt_price_next = if exact_input {
+        let amount_remaining_less_fee = mul_div_floor(
+            amount_remaining,
+            fee_complement,
+            FEE_RATE_DENOMINATOR as u128,
+        )?;
+        amount_in_to_target = if zero_for_one {
+            get_amount0_delta(
+                sqrt_price_target,
+                sqrt_price_current,
+                liquidity,
+                Rounding::Up,
+            )?
+        } else {
+            get_amount1_delta(
+                sqrt_price_current,
+                sqrt_price_target,
+                liquidity,
+                Rounding::Up,
+            )?
+        };
+        if amount_remaining_less_fee >= amount_in_to_target {
+            sqrt_price_target
+        } else {
+            get_next_sqrt_price_from_input(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        }
+    } else {
+        amount_out_to_target = if zero_for_one {
+            get_amount1_delta(
+                sqrt_price_target,
+                sqrt_price_current,
+                liquidity,
+                Rounding::Down,
+            )?
+        } else {
+            get_amount0_delta(
+                sqrt_price_current,
+                sqrt_price_target,
+                liquidity,
+                Rounding::Down,
+            )?
+        };
+        if amount_remaining >= amount_out_to_target {
+            sqrt_price_target
+        } else {
+            get_next_sqrt_price_from_output(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        }
+    };
+
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, mut amount_out) = if zero_for_one {
+        (
+            if reached_target && exact_input {
+                amount_in_to_target
+            } else {
+                get_amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, Rounding::Up)?
+            },
+            if reached_target && !exact_input {
+                amount_out_to_target
+            } else {
+                get_amount1_delta(
+                    sqrt_price_next,
+                    sqrt_price_current,
+                    liquidity,
+                    Rounding::Down,
+                )?
+            },
+        )
+    } else {
+        (
+            if reached_target && exact_input {
+                amount_in_to_target
+            } else {
+                get_amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, Rounding::Up)?
+            },
+            if reached_target && !exact_input {
+                amount_out_to_target
+            } else {
+                get_amount0_delta(
+                    sqrt_price_current,
+                    sqrt_price_next,
+                    liquidity,
+                    Rounding::Down,
+                )?
+            },
+        )
+    };
+
+    // Never pay out more than was asked for in an exact-output swap.
+    if !exact_input && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    // If the whole input was consumed without reaching the target, the remainder is the fee.
+    let fee_amount = if exact_input && !reached_target {
+        amount_remaining - amount_in
+    } else {
+        mul_div_rounding_up(amount_in, fee, fee_complement)?
+    };
 
-    Ok(result_big.to_u128().unwrap_or(0))
+    Ok((sqrt_price_next, amount_in, amount_out, fee_amount))
 }
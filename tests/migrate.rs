@@ -0,0 +1,164 @@
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use swapv3::{
+    state::{Pool, Position, PositionMetadata, Tick, TickBitmap},
+    utils::{SqrtPriceX64, get_sqrt_ratio_at_tick},
+};
+
+/// Returns the data of a pool account as the baseline program wrote it: its
+/// fields in order, then zero padding up to the account size.
+fn baseline_pool(
+    token_0_mint: &Pubkey,
+    token_1_mint: &Pubkey,
+    activity_liquidity: u128,
+    sqrt_price: SqrtPriceX64,
+    current_tick: i32,
+) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(token_0_mint.as_ref());
+    data.extend_from_slice(token_1_mint.as_ref());
+    data.extend_from_slice(&500u32.to_le_bytes());
+    data.extend_from_slice(&10u16.to_le_bytes());
+    data.extend_from_slice(&7u128.to_le_bytes());
+    data.extend_from_slice(&9u128.to_le_bytes());
+    data.extend_from_slice(&activity_liquidity.to_le_bytes());
+    data.extend_from_slice(&sqrt_price.to_bits().to_le_bytes());
+    data.extend_from_slice(&current_tick.to_le_bytes());
+    data.resize(266, 0);
+    data
+}
+
+/// Returns the data of a position account as the baseline program wrote it.
+fn baseline_position(owner: &Pubkey, tick_lower: i32, tick_upper: i32, liquidity: u128) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(owner.as_ref());
+    data.extend_from_slice(&tick_lower.to_le_bytes());
+    data.extend_from_slice(&tick_upper.to_le_bytes());
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&3u128.to_le_bytes());
+    data.extend_from_slice(&4u128.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_baseline_sizes() {
+    assert_eq!(Pool::BASELINE_LEN, 266);
+    assert_eq!(Tick::BASELINE_LEN, 52);
+    assert_eq!(Position::BASELINE_LEN, 88);
+
+    // Migrations are told apart by size alone, so no current account may have
+    // the size of a baseline one.
+    let current = [
+        Pool::LEN,
+        Tick::LEN,
+        Position::LEN,
+        PositionMetadata::LEN,
+        TickBitmap::space(10),
+    ];
+    for baseline in [
+        Pool::BASELINE_LEN,
+        Tick::BASELINE_LEN,
+        Position::BASELINE_LEN,
+    ] {
+        assert!(!current.contains(&baseline));
+    }
+}
+
+#[test]
+fn test_pool_from_baseline() {
+    let token_0_mint = Pubkey::new_unique();
+    let token_1_mint = Pubkey::new_unique();
+    let sqrt_price = get_sqrt_ratio_at_tick(1234).unwrap();
+    // The baseline never updated the current tick.
+    let data = baseline_pool(&token_0_mint, &token_1_mint, 1_000, sqrt_price, 0);
+
+    let pool = Pool::from_baseline(&data).unwrap();
+    assert_eq!(pool.token_0_mint, token_0_mint);
+    assert_eq!(pool.token_1_mint, token_1_mint);
+    assert_eq!(pool.fee, 500);
+    assert_eq!(pool.tick_spacing, 10);
+    assert_eq!(pool.total_fee0, 7);
+    assert_eq!(pool.total_fee1, 9);
+    assert_eq!(pool.sqrt_price, sqrt_price);
+    assert_eq!(pool.current_tick, 1234);
+    // The baseline took no deposits for its liquidity.
+    assert_eq!(pool.activity_liquidity, 0);
+    assert_eq!(pool.fee_growth_global_0_x64, 0);
+    assert_eq!(pool.admin, Pubkey::default());
+    assert_eq!(pool.observations, Pubkey::default());
+    assert_eq!(pool.tick_bitmap, Pubkey::default());
+    assert_eq!(pool.version, Pool::VERSION);
+    assert_eq!(pool.discriminator, Pool::DISCRIMINATOR);
+}
+
+#[test]
+fn test_pool_from_baseline_rejects_other_layouts() {
+    let sqrt_price = get_sqrt_ratio_at_tick(0).unwrap();
+    let data = baseline_pool(
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        0,
+        sqrt_price,
+        0,
+    );
+    for len in [Pool::BASELINE_LEN - 1, Pool::BASELINE_LEN + 1, Pool::LEN] {
+        let mut other = data.clone();
+        other.resize(len, 0);
+        assert_eq!(
+            Pool::from_baseline(&other).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    // The baseline swap moved the price without bounds.
+    let data = baseline_pool(
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        0,
+        SqrtPriceX64::from_bits(u128::MAX),
+        0,
+    );
+    assert!(Pool::from_baseline(&data).is_err());
+}
+
+#[test]
+fn test_position_from_baseline() {
+    let owner = Pubkey::new_unique();
+    let data = baseline_position(&owner, -100, 200, 5_000);
+
+    let position = Position::from_baseline(&data).unwrap();
+    assert_eq!(position.owner, owner);
+    assert_eq!(position.tick_lower, -100);
+    assert_eq!(position.tick_upper, 200);
+    // The baseline took no deposits, and the position belongs to no pool.
+    assert_eq!(position.liquidity, 0);
+    assert_eq!(position.tokens_owed_0, 0);
+    assert_eq!(position.tokens_owed_1, 0);
+    assert_eq!(position.pool, Pubkey::default());
+    assert_eq!(position.position_mint, Pubkey::default());
+    assert!(!position.range_order);
+    assert_eq!(position.version, Position::VERSION);
+    assert_eq!(position.discriminator, Position::DISCRIMINATOR);
+
+    // The upgraded account reads back as a position of the current layout.
+    let mut current = vec![0; Position::LEN];
+    borsh::BorshSerialize::serialize(&position, &mut &mut current[..]).unwrap();
+    let position = Position::unpack(&current).unwrap();
+    assert_eq!(position.owner, owner);
+}
+
+#[test]
+fn test_position_from_baseline_rejects_other_layouts() {
+    let data = baseline_position(&Pubkey::new_unique(), 0, 10, 1);
+    for len in [
+        Position::BASELINE_LEN - 1,
+        Position::BASELINE_LEN + 1,
+        Position::LEN,
+    ] {
+        let mut other = data.clone();
+        other.resize(len, 0);
+        assert_eq!(
+            Position::from_baseline(&other).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+}
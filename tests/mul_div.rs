@@ -0,0 +1,202 @@
+use num_bigint::BigUint;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+use swapv3::utils::{
+    Rounding, compute_swap_step, div_rounding_up, get_amount0_delta, get_amount1_delta,
+    get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, mul_div, mul_div_floor, mul_div_rounding_up,
+};
+
+/// Counts the heap allocations of the current thread, so that tests running in
+/// parallel do not see each other's.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of heap allocations made by `f`.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// The `BigUint` implementation of `mul_div` that the fixed-width one replaced,
+/// kept as a reference for its results and its cost.
+fn mul_div_big(a: u128, b: u128, c: u128, rounding: Rounding) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let product = BigUint::from(a) * BigUint::from(b);
+    let c = BigUint::from(c);
+    let mut quotient = &product / &c;
+    if rounding == Rounding::Up && product % &c != BigUint::from(0u8) {
+        quotient += 1u8;
+    }
+    u128::try_from(&quotient).ok()
+}
+
+/// Operands around the boundaries where the product, quotient or rounding
+/// change width or carry.
+const EDGE_VALUES: [u128; 12] = [
+    0,
+    1,
+    2,
+    3,
+    7,
+    u64::MAX as u128,
+    1 << 64,
+    (1 << 64) + 1,
+    1 << 127,
+    (1 << 127) + 1,
+    u128::MAX - 1,
+    u128::MAX,
+];
+
+#[test]
+fn mul_div_matches_big_integer_reference_on_edge_values() {
+    for a in EDGE_VALUES {
+        for b in EDGE_VALUES {
+            for c in EDGE_VALUES {
+                for rounding in [Rounding::Down, Rounding::Up] {
+                    assert_eq!(
+                        mul_div(a, b, c, rounding).ok(),
+                        mul_div_big(a, b, c, rounding),
+                        "mul_div({a}, {b}, {c}, {rounding:?})"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn mul_div_rounds_only_inexact_quotients_up() {
+    // 7 * 3 / 2 = 10.5
+    assert_eq!(mul_div_floor(7, 3, 2).unwrap(), 10);
+    assert_eq!(mul_div_rounding_up(7, 3, 2).unwrap(), 11);
+    // 6 * 3 / 2 = 9 exactly.
+    assert_eq!(mul_div_floor(6, 3, 2).unwrap(), 9);
+    assert_eq!(mul_div_rounding_up(6, 3, 2).unwrap(), 9);
+    // A remainder of one in a 256-bit product still rounds up.
+    let a = u128::MAX;
+    assert_eq!(mul_div_floor(a, 3, 4).unwrap(), a / 4 * 3 + 2);
+    assert_eq!(mul_div_rounding_up(a, 3, 4).unwrap(), a / 4 * 3 + 3);
+    assert_eq!(mul_div_floor(0, a, 1).unwrap(), 0);
+    assert_eq!(mul_div_rounding_up(0, a, 1).unwrap(), 0);
+}
+
+#[test]
+fn mul_div_handles_products_wider_than_u128() {
+    let a = u128::MAX;
+    assert_eq!(mul_div_floor(a, a, a).unwrap(), a);
+    assert_eq!(mul_div_rounding_up(a, a, a).unwrap(), a);
+    assert_eq!(mul_div_floor(a, 1 << 64, 1 << 64).unwrap(), a);
+    assert_eq!(
+        mul_div_floor(1 << 100, 1 << 100, 1 << 80).unwrap(),
+        1 << 120
+    );
+}
+
+#[test]
+fn mul_div_errors_on_zero_denominator_and_overflow() {
+    assert!(mul_div_floor(1, 1, 0).is_err());
+    assert!(mul_div_rounding_up(1, 1, 0).is_err());
+    assert!(div_rounding_up(1, 0).is_err());
+    // The quotient itself no longer fits in a u128.
+    assert!(mul_div_floor(u128::MAX, 2, 1).is_err());
+    assert!(mul_div_rounding_up(u128::MAX, 2, 1).is_err());
+
+    // (2^129 - 1) / 2 is u128::MAX with a remainder of one: the floor fits,
+    // but rounding it up does not.
+    let a = (u128::MAX / 7) * 2 + 1;
+    assert_eq!(mul_div_floor(a, 7, 2).unwrap(), u128::MAX);
+    assert!(mul_div_rounding_up(a, 7, 2).is_err());
+}
+
+#[test]
+fn div_rounding_up_rounds_only_inexact_quotients_up() {
+    assert_eq!(div_rounding_up(0, 3).unwrap(), 0);
+    assert_eq!(div_rounding_up(9, 3).unwrap(), 3);
+    assert_eq!(div_rounding_up(10, 3).unwrap(), 4);
+    assert_eq!(div_rounding_up(u128::MAX, 1).unwrap(), u128::MAX);
+    assert_eq!(div_rounding_up(u128::MAX, 2).unwrap(), 1 << 127);
+}
+
+/// On SBF, every heap allocation is paid for in compute units and comes out of
+/// a 32 KiB bump heap that is never freed, so the swap math must not allocate.
+#[test]
+fn swap_math_does_not_allocate() {
+    let lower = get_sqrt_ratio_at_tick(-1000).unwrap();
+    let upper = get_sqrt_ratio_at_tick(1000).unwrap();
+
+    let count = allocations(|| {
+        for tick in [-443_636, -1000, 0, 1, 1000, 443_636] {
+            let sqrt_price = black_box(get_sqrt_ratio_at_tick(tick).unwrap());
+            black_box(get_tick_at_sqrt_ratio(sqrt_price).ok());
+        }
+        black_box(mul_div(u128::MAX, u128::MAX - 1, u128::MAX, Rounding::Up).unwrap());
+        black_box(get_amount0_delta(lower, upper, u64::MAX as u128, Rounding::Up).unwrap());
+        black_box(get_amount1_delta(lower, upper, u64::MAX as u128, Rounding::Down).unwrap());
+        black_box(compute_swap_step(upper, lower, 1 << 80, 1 << 70, true, 500).unwrap());
+    });
+    assert_eq!(count, 0);
+}
+
+/// Counts the heap allocations of the fixed-width `mul_div` and of the
+/// `BigUint` one it replaced, and prints their native running times. This
+/// meters no compute units, which only SBF can do, and the native times say
+/// little about on-chain cost; the allocation counts are what is asserted.
+#[test]
+fn fixed_width_mul_div_allocates_less_than_big_integer() {
+    const ITERATIONS: u128 = 10_000;
+    let inputs = (1..=ITERATIONS).map(|i| (u128::MAX / i, u128::MAX - i, (1 << 100) + i));
+
+    let mut fixed_width_allocations = 0;
+    let mut big_integer_allocations = 0;
+    let mut fixed_width_time = Duration::ZERO;
+    let mut big_integer_time = Duration::ZERO;
+    for (a, b, c) in inputs {
+        let start = Instant::now();
+        fixed_width_allocations += allocations(|| {
+            black_box(mul_div(black_box(a), black_box(b), black_box(c), Rounding::Up).ok());
+        });
+        fixed_width_time += start.elapsed();
+
+        let start = Instant::now();
+        big_integer_allocations += allocations(|| {
+            black_box(mul_div_big(
+                black_box(a),
+                black_box(b),
+                black_box(c),
+                Rounding::Up,
+            ));
+        });
+        big_integer_time += start.elapsed();
+    }
+
+    println!(
+        "mul_div x{ITERATIONS}: U256 {fixed_width_allocations} allocations in {fixed_width_time:?}, \
+         BigUint {big_integer_allocations} allocations in {big_integer_time:?}"
+    );
+    assert_eq!(fixed_width_allocations, 0);
+    assert!(big_integer_allocations >= ITERATIONS as usize);
+}
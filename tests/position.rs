@@ -0,0 +1,247 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use swapv3::{
+    error::SwapV3Error,
+    instruction::SwapV3Instruction,
+    processor::Processor,
+    state::{Observation, Observations, Pool, Position, PositionMetadata, Tick, TickBitmap},
+};
+
+const TICK_SPACING: u16 = 10;
+
+/// An account owned by the test, from which `AccountInfo`s are borrowed.
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_signer: bool,
+}
+
+impl Account {
+    fn new(owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key: Pubkey::new_unique(),
+            owner,
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            is_signer: false,
+        }
+    }
+
+    fn signer() -> Self {
+        Self {
+            is_signer: true,
+            ..Self::new(solana_program::system_program::id(), Vec::new())
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Returns the data of a pool account with the current layout.
+fn pool_data() -> Vec<u8> {
+    let mut data = vec![0; Pool::LEN];
+    let mut pool = Pool::deserialize(&mut &data[..]).unwrap();
+    pool.tick_spacing = TICK_SPACING;
+    pool.version = Pool::VERSION;
+    pool.discriminator = Pool::DISCRIMINATOR;
+    pool.serialize(&mut &mut data[..]).unwrap();
+    data
+}
+
+/// Returns the data of a tick account with the current layout.
+fn tick_data() -> Vec<u8> {
+    let mut data = vec![0; Tick::LEN];
+    let mut tick = Tick::deserialize(&mut &data[..]).unwrap();
+    tick.version = Tick::VERSION;
+    tick.discriminator = Tick::DISCRIMINATOR;
+    tick.serialize(&mut &mut data[..]).unwrap();
+    data
+}
+
+/// Returns the data of an empty tick bitmap account with the current layout.
+fn tick_bitmap_data() -> Vec<u8> {
+    let mut data = vec![0; TickBitmap::space(TICK_SPACING)];
+    *data.last_mut().unwrap() = TickBitmap::VERSION;
+    data
+}
+
+/// Returns the data of an observations account with the current layout.
+fn observations_data() -> Vec<u8> {
+    let observations = Observations {
+        observations: vec![Observation::default(); 8],
+        version: Observations::VERSION,
+    };
+    observations.try_to_vec().unwrap()
+}
+
+/// Opens a position at `position`, returning the error the program fails with.
+fn open_position(program_id: &Pubkey, position: &mut Account) -> ProgramError {
+    let mut pool = Account::new(*program_id, pool_data());
+    let mut position_mint = Account::new(spl_token::id(), vec![0; spl_token::state::Mint::LEN]);
+    let mut recipient = Account::new(spl_token::id(), vec![0; spl_token::state::Account::LEN]);
+    let mut metadata = Account::new(solana_program::system_program::id(), Vec::new());
+    let mut owner = Account::signer();
+    let mut payer = Account::signer();
+    let mut vault_authority = Account::new(*program_id, Vec::new());
+    let mut token_program = Account::new(Pubkey::default(), Vec::new());
+    token_program.key = spl_token::id();
+    let mut system_program = Account::new(Pubkey::default(), Vec::new());
+    system_program.key = solana_program::system_program::id();
+
+    let accounts = [
+        pool.info(),
+        position.info(),
+        position_mint.info(),
+        recipient.info(),
+        metadata.info(),
+        owner.info(),
+        payer.info(),
+        vault_authority.info(),
+        token_program.info(),
+        system_program.info(),
+    ];
+    let data = SwapV3Instruction::OpenPosition {
+        tick_lower: -100,
+        tick_upper: 100,
+    }
+    .try_to_vec()
+    .unwrap();
+    Processor::process(program_id, &accounts, &data).unwrap_err()
+}
+
+#[test]
+fn test_open_position_rejects_other_program_accounts() {
+    let program_id = Pubkey::new_unique();
+    for data in [tick_bitmap_data(), tick_data(), observations_data()] {
+        let mut account = Account::new(program_id, data);
+        assert_eq!(
+            open_position(&program_id, &mut account),
+            ProgramError::InvalidAccountData
+        );
+    }
+}
+
+#[test]
+fn test_open_position_rejects_written_position() {
+    let program_id = Pubkey::new_unique();
+    let mut data = vec![0; Position::LEN];
+    data[0] = 1;
+    let mut account = Account::new(program_id, data);
+    assert_eq!(
+        open_position(&program_id, &mut account),
+        ProgramError::AccountAlreadyInitialized
+    );
+}
+
+#[test]
+fn test_position_unpack_rejects_other_program_accounts() {
+    for data in [tick_bitmap_data(), tick_data(), observations_data()] {
+        assert!(!Position::is_new(&data));
+        assert!(Position::unpack(&data).is_err());
+    }
+    // A position-sized account written by the program is a position only if
+    // it carries the position discriminator.
+    let mut data = vec![0; Position::LEN];
+    data[Position::VERSION_OFFSET] = Position::VERSION;
+    assert_eq!(
+        Position::unpack(&data).unwrap_err(),
+        ProgramError::InvalidAccountData
+    );
+    data[Position::DISCRIMINATOR_OFFSET] = Position::DISCRIMINATOR;
+    assert!(Position::unpack(&data).is_ok());
+    assert!(Position::unpack(&vec![0; Position::LEN]).is_ok());
+}
+
+/// Removes `liquidity_amount` from a position holding `liquidity`, returning
+/// the error the program fails with.
+fn remove_liquidity(liquidity: u128, liquidity_amount: u128) -> ProgramError {
+    let program_id = Pubkey::new_unique();
+    let mut owner = Account::signer();
+    let mut observations = Account::new(program_id, observations_data());
+    let mut tick_bitmap = Account::new(program_id, tick_bitmap_data());
+    let mut pool = Account::new(program_id, Vec::new());
+    let mut pool_state = Pool::deserialize(&mut &pool_data()[..]).unwrap();
+    pool_state.observations = observations.key;
+    pool_state.tick_bitmap = tick_bitmap.key;
+    pool.data = pool_state.try_to_vec().unwrap();
+
+    let position_mint = Pubkey::new_unique();
+    let mut position = Account::new(program_id, vec![0; Position::LEN]);
+    let mut position_state = Position::unpack(&position.data).unwrap();
+    position_state.owner = owner.key;
+    position_state.pool = pool.key;
+    position_state.position_mint = position_mint;
+    position_state.tick_lower = -100;
+    position_state.tick_upper = 100;
+    position_state.liquidity = liquidity;
+    position_state.version = Position::VERSION;
+    position_state.discriminator = Position::DISCRIMINATOR;
+    position.data = position_state.try_to_vec().unwrap();
+
+    let mut token_account = Account::new(spl_token::id(), vec![0; spl_token::state::Account::LEN]);
+    spl_token::state::Account {
+        mint: position_mint,
+        owner: owner.key,
+        amount: 1,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut token_account.data);
+    let metadata_state = PositionMetadata {
+        pool: pool.key,
+        position: position.key,
+        position_mint,
+        tick_lower: -100,
+        tick_upper: 100,
+        version: PositionMetadata::VERSION,
+        discriminator: PositionMetadata::DISCRIMINATOR,
+    };
+    let mut metadata = Account::new(program_id, metadata_state.try_to_vec().unwrap());
+    metadata.key = PositionMetadata::find_address(&position_mint, &program_id).0;
+    let mut tick_lower = Account::new(program_id, tick_data());
+    let mut tick_upper = Account::new(program_id, tick_data());
+
+    let accounts = [
+        pool.info(),
+        position.info(),
+        tick_lower.info(),
+        tick_upper.info(),
+        owner.info(),
+        observations.info(),
+        tick_bitmap.info(),
+        token_account.info(),
+        metadata.info(),
+    ];
+    let data = SwapV3Instruction::RemoveLiquidity { liquidity_amount }
+        .try_to_vec()
+        .unwrap();
+    Processor::process(&program_id, &accounts, &data).unwrap_err()
+}
+
+#[test]
+fn test_remove_liquidity_rejects_more_than_position_holds() {
+    assert_eq!(
+        remove_liquidity(1_000, 1_001),
+        ProgramError::from(SwapV3Error::InsufficientLiquidity)
+    );
+    assert_eq!(
+        remove_liquidity(0, 1),
+        ProgramError::from(SwapV3Error::InsufficientLiquidity)
+    );
+}
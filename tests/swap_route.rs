@@ -0,0 +1,129 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use swapv3::{
+    error::SwapV3Error, instruction::SwapV3Instruction, processor::Processor, state::Pool,
+};
+
+/// An account owned by the test, from which `AccountInfo`s are borrowed.
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_signer: bool,
+}
+
+impl Account {
+    fn new(owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key: Pubkey::new_unique(),
+            owner,
+            lamports: 0,
+            data,
+            is_signer: false,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Returns the data of a pool account swapping `token_0_mint` for `token_1_mint`.
+fn pool_data(token_0_mint: &Pubkey, token_1_mint: &Pubkey) -> Vec<u8> {
+    let mut data = vec![0; Pool::LEN];
+    let mut pool = Pool::deserialize(&mut &data[..]).unwrap();
+    pool.token_0_mint = *token_0_mint;
+    pool.token_1_mint = *token_1_mint;
+    pool.tick_spacing = 10;
+    pool.version = Pool::VERSION;
+    pool.discriminator = Pool::DISCRIMINATOR;
+    pool.serialize(&mut &mut data[..]).unwrap();
+    data
+}
+
+/// Runs `SwapRoute` with `input_mint` through pools with the given mints,
+/// passing no tick accounts, and returns the result with the pools' data after.
+fn swap_route(input_mint: &Pubkey, pools: &[(Pubkey, Pubkey)]) -> (ProgramResult, Vec<Vec<u8>>) {
+    let program_id = Pubkey::new_unique();
+    let system = solana_program::system_program::id();
+    let mut user = Account::new(system, Vec::new());
+    user.is_signer = true;
+    let mut accounts = vec![
+        user,
+        Account::new(spl_token::id(), Vec::new()),
+        Account::new(spl_token::id(), Vec::new()),
+        Account::new(system, Vec::new()),
+        Account::new(spl_token::id(), Vec::new()),
+    ];
+    accounts[4].key = *input_mint;
+    for (token_0_mint, token_1_mint) in pools {
+        accounts.push(Account::new(
+            program_id,
+            pool_data(token_0_mint, token_1_mint),
+        ));
+        accounts.extend((0..7).map(|_| Account::new(system, Vec::new())));
+    }
+
+    let data = SwapV3Instruction::SwapRoute {
+        amount_in: 1_000,
+        min_amount_out: 0,
+        tick_counts: vec![0; pools.len()],
+    }
+    .try_to_vec()
+    .unwrap();
+    let infos: Vec<_> = accounts.iter_mut().map(Account::info).collect();
+    let result = Processor::process(&program_id, &infos, &data);
+    drop(infos);
+
+    let pools = accounts[5..]
+        .iter()
+        .step_by(8)
+        .map(|pool| pool.data.clone())
+        .collect();
+    (result, pools)
+}
+
+#[test]
+fn test_swap_route_rejects_reversed_pool() {
+    let token_a = Pubkey::new_unique();
+    let token_b = Pubkey::new_unique();
+    let token_c = Pubkey::new_unique();
+
+    // The second pool holds B as its token_1, so B could only be swapped into
+    // it in the direction no pool supports.
+    let pools = [(token_a, token_b), (token_c, token_b)];
+    let (result, after) = swap_route(&token_a, &pools);
+    assert_eq!(
+        result.unwrap_err(),
+        ProgramError::from(SwapV3Error::RouteMintMismatch)
+    );
+    // The route is rejected before the first hop is swapped.
+    assert_eq!(after[0], pool_data(&token_a, &token_b));
+    assert_eq!(after[1], pool_data(&token_c, &token_b));
+}
+
+#[test]
+fn test_swap_route_rejects_wrong_input_mint() {
+    let token_a = Pubkey::new_unique();
+    let token_b = Pubkey::new_unique();
+
+    // The input is the first pool's token_1.
+    let (result, _) = swap_route(&token_b, &[(token_a, token_b)]);
+    assert_eq!(
+        result.unwrap_err(),
+        ProgramError::from(SwapV3Error::RouteMintMismatch)
+    );
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+use swapv3::state::TickBitmap;
+use swapv3::tick_bitmap::{next_initialized_tick_within_one_word, position, set};
+use swapv3::utils::{MAX_TICK, MIN_TICK};
+
+/// Returns an empty tick bitmap, with its version byte written.
+fn new_bitmap(tick_spacing: u16) -> Vec<u8> {
+    let mut data = vec![0; TickBitmap::space(tick_spacing)];
+    *data.last_mut().unwrap() = TickBitmap::VERSION;
+    data
+}
+
+/// Walks the bitmap down from `tick` the way a swap does, returning every
+/// initialized tick it stops at.
+fn walk_down(words: &[u8], mut tick: i32, tick_spacing: u16) -> Vec<i32> {
+    let mut found = Vec::new();
+    while tick > MIN_TICK {
+        let (next, initialized) =
+            next_initialized_tick_within_one_word(words, tick, tick_spacing).unwrap();
+        assert!(next <= tick);
+        if initialized {
+            found.push(next);
+        }
+        tick = next - 1;
+    }
+    found
+}
+
+#[test]
+fn position_rejects_ticks_off_spacing_or_out_of_range() {
+    assert!(position(5, 10).is_err());
+    assert!(position(0, 0).is_err());
+    assert!(position(MIN_TICK - 1, 1).is_err());
+    assert!(position(MAX_TICK + 1, 1).is_err());
+    assert_eq!(position(MIN_TICK, 1).unwrap(), (0, 0));
+    let (word, bit) = position(MAX_TICK, 1).unwrap();
+    assert_eq!(word * 64 + bit, (MAX_TICK - MIN_TICK) as usize);
+    assert!(word < TickBitmap::word_count(1));
+}
+
+#[test]
+fn walk_finds_every_initialized_tick_in_order() {
+    for tick_spacing in [1u16, 10, 60, 200] {
+        let spacing = tick_spacing as i32;
+        let mut data = new_bitmap(tick_spacing);
+        let words = TickBitmap::words_mut(&mut data, tick_spacing).unwrap();
+
+        // A spread of ticks, including both ends of the range and neighbours
+        // on either side of word boundaries.
+        let mut initialized = BTreeSet::new();
+        let mut seed = 0x2545_f491_u64;
+        for _ in 0..200 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let tick = MIN_TICK + (seed >> 33) as i32 % (MAX_TICK - MIN_TICK);
+            initialized.insert(tick - tick.rem_euclid(spacing));
+        }
+        for tick in [MIN_TICK, MAX_TICK, 0, -spacing, 63 * spacing, 64 * spacing] {
+            initialized.insert(tick - tick.rem_euclid(spacing));
+        }
+        initialized.retain(|tick| (MIN_TICK..=MAX_TICK).contains(tick));
+        for &tick in &initialized {
+            set(words, tick, tick_spacing, true).unwrap();
+        }
+
+        let expected: Vec<i32> = initialized.iter().rev().copied().collect();
+        assert_eq!(walk_down(words, MAX_TICK, tick_spacing), expected);
+
+        // Starting between ticks finds only those at or below the start.
+        let start = 5 * spacing + spacing / 2;
+        let expected: Vec<i32> = initialized.range(..=start).rev().copied().collect();
+        assert_eq!(walk_down(words, start, tick_spacing), expected);
+
+        // Clearing a tick removes it from the walk.
+        let cleared = *initialized.iter().nth(initialized.len() / 2).unwrap();
+        set(words, cleared, tick_spacing, false).unwrap();
+        initialized.remove(&cleared);
+        let expected: Vec<i32> = initialized.iter().rev().copied().collect();
+        assert_eq!(walk_down(words, MAX_TICK, tick_spacing), expected);
+    }
+}
+
+#[test]
+fn words_rejects_other_sizes_and_versions() {
+    let mut data = new_bitmap(10);
+    assert!(TickBitmap::words(&data, 10).is_ok());
+    assert!(TickBitmap::words(&data, 60).is_err());
+    *data.last_mut().unwrap() = 0;
+    assert!(TickBitmap::words(&data, 10).is_err());
+}
@@ -0,0 +1,127 @@
+use num_bigint::BigUint;
+use swapv3::utils::{
+    MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK, SqrtPriceX64, get_sqrt_ratio_at_tick,
+    get_tick_at_sqrt_ratio,
+};
+
+/// Every tick's sqrt price maps back to the tick, and the price one unit below
+/// it maps to the tick below, so `get_tick_at_sqrt_ratio` is the exact floor
+/// inverse of `get_sqrt_ratio_at_tick` across the whole tick range.
+#[test]
+fn tick_at_sqrt_ratio_round_trips_every_tick() {
+    for tick in MIN_TICK..MAX_TICK {
+        let sqrt_price = get_sqrt_ratio_at_tick(tick).unwrap();
+        assert_eq!(get_tick_at_sqrt_ratio(sqrt_price).unwrap(), tick);
+        if tick > MIN_TICK {
+            let below = SqrtPriceX64::from_bits(sqrt_price.to_bits() - 1);
+            assert_eq!(
+                get_tick_at_sqrt_ratio(below).unwrap(),
+                tick - 1,
+                "tick {tick}"
+            );
+        }
+    }
+    let below_max = SqrtPriceX64::from_bits(MAX_SQRT_RATIO.to_bits() - 1);
+    assert_eq!(get_tick_at_sqrt_ratio(below_max).unwrap(), MAX_TICK - 1);
+}
+
+/// Prices outside `MIN_SQRT_RATIO..MAX_SQRT_RATIO` have no tick.
+#[test]
+fn tick_at_sqrt_ratio_rejects_prices_out_of_range() {
+    assert_eq!(get_tick_at_sqrt_ratio(MIN_SQRT_RATIO).unwrap(), MIN_TICK);
+    assert!(get_tick_at_sqrt_ratio(MAX_SQRT_RATIO).is_err());
+    let below_min = SqrtPriceX64::from_bits(MIN_SQRT_RATIO.to_bits() - 1);
+    assert!(get_tick_at_sqrt_ratio(below_min).is_err());
+    assert!(get_tick_at_sqrt_ratio(SqrtPriceX64::from_bits(0)).is_err());
+    assert!(get_tick_at_sqrt_ratio(SqrtPriceX64::from_bits(u128::MAX)).is_err());
+}
+
+/// The exact sqrt price of every tick, `sqrt(1.0001)^tick`, as Q64.192 numbers,
+/// computed by repeated multiplication from tick 0 outwards. Each step truncates
+/// by less than 2^-192, so after 443636 steps the values are still accurate to
+/// far better than the 2^-64 a Q64.64 sqrt price can resolve.
+struct ReferenceSqrtPrices {
+    step_up: BigUint,
+    step_down: BigUint,
+}
+
+impl ReferenceSqrtPrices {
+    const FRACTION_BITS: u32 = 192;
+
+    fn new() -> Self {
+        let one = BigUint::from(1u8) << (2 * Self::FRACTION_BITS);
+        // sqrt(1.0001) and its inverse, as Q1.192 numbers.
+        let step_up = (&one * 10001u32 / 10000u32).sqrt();
+        let step_down = &one / &step_up;
+        Self { step_up, step_down }
+    }
+
+    /// Calls `f` with each tick from 0 up to `last`, or down to `last` when it
+    /// is negative, and the tick's sqrt price.
+    fn for_each(&self, last: i32, mut f: impl FnMut(i32, &BigUint)) {
+        let (step, direction) = if last >= 0 {
+            (&self.step_up, 1)
+        } else {
+            (&self.step_down, -1)
+        };
+        let mut sqrt_price = BigUint::from(1u8) << Self::FRACTION_BITS;
+        let mut tick = 0;
+        loop {
+            f(tick, &sqrt_price);
+            if tick == last {
+                break;
+            }
+            sqrt_price = (sqrt_price * step) >> Self::FRACTION_BITS;
+            tick += direction;
+        }
+    }
+}
+
+/// Every tick's sqrt price is never below the exact value and at most two
+/// Q64.64 units above it, and the bounds and their constants are where the
+/// reference puts them.
+#[test]
+fn sqrt_ratio_at_tick_matches_high_precision_reference() {
+    let reference = ReferenceSqrtPrices::new();
+    // The price 1.0001^tick stays within [2^-64, 2^64] up to the bounds, and
+    // leaves it right after them: the sqrt price crosses 2^32 or 2^-32.
+    let sqrt_price_bound = BigUint::from(1u8) << (ReferenceSqrtPrices::FRACTION_BITS + 32);
+    let inverse_sqrt_price_bound = BigUint::from(1u8) << (ReferenceSqrtPrices::FRACTION_BITS - 32);
+
+    for last in [MAX_TICK + 1, MIN_TICK - 1] {
+        reference.for_each(last, |tick, exact| {
+            if tick == MAX_TICK + 1 {
+                assert!(*exact > sqrt_price_bound);
+                assert!(get_sqrt_ratio_at_tick(tick).is_err());
+                return;
+            }
+            if tick == MIN_TICK - 1 {
+                assert!(*exact < inverse_sqrt_price_bound);
+                assert!(get_sqrt_ratio_at_tick(tick).is_err());
+                return;
+            }
+            if tick == MAX_TICK {
+                assert!(*exact <= sqrt_price_bound);
+            }
+            if tick == MIN_TICK {
+                assert!(*exact >= inverse_sqrt_price_bound);
+            }
+
+            // Rounded up, so above the exact value unless that is a whole Q64.64 number.
+            let shift = ReferenceSqrtPrices::FRACTION_BITS - 64;
+            let floor = u128::try_from(exact >> shift).unwrap();
+            let exact_is_whole = exact
+                .trailing_zeros()
+                .is_none_or(|zeros| zeros >= shift as u64);
+            let lowest = if exact_is_whole { floor } else { floor + 1 };
+            let sqrt_price = get_sqrt_ratio_at_tick(tick).unwrap().to_bits();
+            assert!(
+                (lowest..=floor + 2).contains(&sqrt_price),
+                "tick {tick}: {sqrt_price}, exact floor {floor}"
+            );
+        });
+    }
+
+    assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), MIN_SQRT_RATIO);
+    assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), MAX_SQRT_RATIO);
+}